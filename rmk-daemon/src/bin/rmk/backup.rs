@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+use rmk_fs::{export, ExportFormat, ExportOptions};
+
+use crate::library::open;
+
+/// Exports the whole library to a folder, only rendering what changed since
+/// the previous backup to the same folder.
+pub fn backup(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let target = PathBuf::from(args.value_of("target").unwrap());
+
+    let mut options = ExportOptions {
+        format: match args.value_of("format") {
+            Some("svg") => ExportFormat::Svg,
+            Some("png") => ExportFormat::Png,
+            _ => ExportFormat::Pdf,
+        },
+        scale: args.value_of("scale").unwrap().parse()?,
+        trash: args.is_present("trash"),
+        keep_removed: args.is_present("keep-removed"),
+        ..Default::default()
+    };
+    if let Some(jobs) = args.value_of("jobs") {
+        options.jobs = jobs.parse()?;
+    }
+
+    let report = export(&table, &target, &options)?;
+
+    for (id, e) in &report.failed {
+        let path = table.path(id).unwrap_or_else(|| id.clone());
+        eprintln!("Failed to export {}: {}", path, e);
+    }
+    println!("{}", report);
+
+    if !report.failed.is_empty() {
+        bail!("{} documents could not be exported", report.failed.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use clap::ArgMatches;
+use rmk_fs::Snapshots;
+use rmk_notebook::{diff::diff as diff_notebooks, read_notebook};
+
+use crate::library::{open, resolve};
+
+/// Compares a notebook between two xochitl folders or two snapshots, the
+/// newer one being the library itself by default.
+pub fn diff(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let id = resolve(&table, args.value_of("document").unwrap())?;
+
+    let old = args.value_of("old").unwrap();
+    let new = args.value_of("new");
+
+    let (old_root, new_root) = match args.value_of("snapshots") {
+        Some(dir) => {
+            let snapshots = Snapshots::open(&PathBuf::from(dir))?;
+            let new_root = match new {
+                Some(new) => snapshots.checkout(new, &id)?,
+                None => table.root(),
+            };
+            (snapshots.checkout(old, &id)?, new_root)
+        }
+        None => (
+            PathBuf::from(old),
+            new.map_or_else(|| table.root(), PathBuf::from),
+        ),
+    };
+
+    let old = read_notebook(&old_root, &id)?;
+    let new = read_notebook(&new_root, &id)?;
+    let changes = diff_notebooks(&old, &new);
+
+    if changes.is_empty() {
+        println!("No changes");
+    } else {
+        print!("{}", changes);
+    }
+
+    if let Some(path) = args.value_of("pdf") {
+        let mut file = BufWriter::new(File::create(path)?);
+        changes.render(&old, &new, &mut file)?;
+        println!("Diff written to {}", path);
+    }
+
+    if let Some(dir) = args.value_of("svg") {
+        let dir = PathBuf::from(dir);
+        create_dir_all(&dir)?;
+
+        for page in 0..changes.pages.len() {
+            let path = dir.join(format!("{:03}.svg", page + 1));
+            let mut file = BufWriter::new(File::create(&path)?);
+            changes.render_svg(page, &old, &new, &mut file)?;
+        }
+        println!("Diff written to {}", dir.display());
+    }
+
+    Ok(())
+}
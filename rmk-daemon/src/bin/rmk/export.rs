@@ -0,0 +1,44 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+use rmk_fs::render_pdf;
+
+use crate::library::{open, resolve};
+
+pub fn export(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let document = args.value_of("document").unwrap();
+    let id = resolve(&table, document)?;
+
+    match table.get(&id) {
+        Some(metadata) if !metadata.is_collection() => {}
+        _ => bail!("not a document: {}", document),
+    }
+
+    let pdf = render_pdf(&table.root(), &id)?;
+
+    // Named as in the mount, already escaped into a valid file name
+    let name = table
+        .path(&id)
+        .and_then(|path| path.rsplit('/').next().map(|name| format!("{}.pdf", name)))
+        .unwrap_or_else(|| format!("{}.pdf", id));
+
+    let output = match args.value_of("output") {
+        Some("-") => {
+            io::stdout().write_all(&pdf)?;
+            return Ok(());
+        }
+        Some(output) if PathBuf::from(output).is_dir() => PathBuf::from(output).join(name),
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(name),
+    };
+
+    std::fs::write(&output, &pdf)?;
+    println!("{}", output.display());
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+
+use crate::library::{open, resolve_folder};
+
+pub fn import(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let parent = resolve_folder(&table, args.value_of("parent"))?;
+    let files: Vec<PathBuf> = args
+        .values_of("files")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect();
+
+    let name = args.value_of("name");
+    if name.is_some() && files.len() > 1 {
+        bail!("--name needs a single file");
+    }
+
+    for file in files {
+        let id = rmk_notebook::import::import(&table.root(), &file, &parent, name)?;
+        println!("{}  {}", id, file.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::{path::PathBuf, time::SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local};
+use clap::ArgMatches;
+use rmk_fs::{RmkTable, ROOT_ID, TRASH_ID};
+use rmk_notebook::Metadata;
+
+pub fn root(args: &ArgMatches) -> Result<PathBuf> {
+    match args.value_of("root") {
+        Some(root) => Ok(PathBuf::from(root)),
+        None => bail!("no xochitl folder, pass --root or set RMK_ROOT"),
+    }
+}
+
+/// The library at `--root`, scanned. Files that cannot be read are logged
+/// by the scan and left out.
+pub fn open(args: &ArgMatches) -> Result<RmkTable> {
+    let table = RmkTable::new(&root(args)?);
+    table.scan()?;
+
+    Ok(table)
+}
+
+/// Id of `document`, given as an id or as a path such as `/Work/Notes`.
+pub fn resolve(table: &RmkTable, document: &str) -> Result<String> {
+    if table.get(document).is_some() {
+        return Ok(document.to_string());
+    }
+
+    table
+        .find(document)
+        .ok_or_else(|| anyhow!("no such document: {}", document))
+}
+
+/// Id of `folder`, the top level when not given.
+pub fn resolve_folder(table: &RmkTable, folder: Option<&str>) -> Result<String> {
+    let id = match folder {
+        Some(folder) => resolve(table, folder)?,
+        None => return Ok(ROOT_ID.to_string()),
+    };
+
+    if id != ROOT_ID && id != TRASH_ID && !table.get(&id).is_some_and(|m| m.is_collection()) {
+        bail!("not a folder: {}", folder.unwrap_or_default());
+    }
+
+    Ok(id)
+}
+
+/// `folder` for folders, the file type for documents.
+pub fn document_type(table: &RmkTable, id: &str, metadata: &Metadata) -> String {
+    if metadata.is_collection() {
+        return "folder".to_string();
+    }
+
+    table
+        .content(id)
+        .ok()
+        .and_then(|content| content.file_type)
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
@@ -0,0 +1,166 @@
+use std::time::SystemTime;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use rmk_fs::{Entry, RmkTable};
+use serde_json::{json, Value};
+
+use crate::library::{document_type, format_time, open, resolve, resolve_folder};
+
+pub fn ls(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let folder = resolve_folder(&table, args.value_of("folder"))?;
+    let entries = table.children(&folder);
+
+    if args.value_of("format") == Some("json") {
+        let entries: Vec<Value> = entries
+            .iter()
+            .map(|entry| entry_json(&table, &entry.id))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    for entry in entries {
+        if args.is_present("long") {
+            let pages = match table.content(&entry.id) {
+                Ok(content) if !entry.metadata.is_collection() => content.page_count.to_string(),
+                _ => "-".to_string(),
+            };
+
+            println!(
+                "{:<8} {:>5}  {}  {}  {}",
+                document_type(&table, &entry.id, &entry.metadata),
+                pages,
+                format_time(entry.metadata.last_modified),
+                entry.id,
+                display_name(&entry)
+            );
+        } else {
+            println!("{}", display_name(&entry));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn tree(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let folder = resolve_folder(&table, args.value_of("folder"))?;
+    let depth = match args.value_of("depth") {
+        Some(depth) => depth.parse()?,
+        None => usize::MAX,
+    };
+
+    println!("{}", table.path(&folder).unwrap_or_else(|| "/".to_string()));
+    print_tree(&table, &folder, "", depth, args.is_present("ids"));
+
+    Ok(())
+}
+
+fn print_tree(table: &RmkTable, folder: &str, prefix: &str, depth: usize, ids: bool) {
+    if depth == 0 {
+        return;
+    }
+
+    let entries = table.children(folder);
+    for (index, entry) in entries.iter().enumerate() {
+        let last = index + 1 == entries.len();
+
+        if ids {
+            println!(
+                "{}{}{}  {}",
+                prefix,
+                if last { "└── " } else { "├── " },
+                display_name(entry),
+                entry.id
+            );
+        } else {
+            println!(
+                "{}{}{}",
+                prefix,
+                if last { "└── " } else { "├── " },
+                display_name(entry)
+            );
+        }
+
+        if entry.metadata.is_collection() {
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            print_tree(table, &entry.id, &prefix, depth - 1, ids);
+        }
+    }
+}
+
+pub fn info(args: &ArgMatches) -> Result<()> {
+    let table = open(args)?;
+    let id = resolve(&table, args.value_of("document").unwrap())?;
+    let info = entry_json(&table, &id);
+
+    if args.value_of("format") == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    if let Value::Object(fields) = info {
+        for (key, value) in fields {
+            let value = match value {
+                Value::Null => "-".to_string(),
+                Value::String(value) => value,
+                Value::Array(values) => values
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value => value.to_string(),
+            };
+
+            println!("{:<14} {}", format!("{}:", key), value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Folders are shown with a trailing slash, as `ls -F` does.
+fn display_name(entry: &Entry) -> String {
+    if entry.metadata.is_collection() {
+        format!("{}/", entry.name)
+    } else {
+        entry.name.clone()
+    }
+}
+
+/// Metadata and content of `id`, with the same names as the `metadata`
+/// table columns.
+fn entry_json(table: &RmkTable, id: &str) -> Value {
+    let metadata = match table.get(id) {
+        Some(metadata) => metadata,
+        None => return Value::Null,
+    };
+    let content = table.content(id).ok();
+
+    json!({
+        "id": id,
+        "name": metadata.visible_name,
+        "type": document_type(table, id, &metadata),
+        "path": table.path(id),
+        "parent": metadata.parent,
+        "last_modified": timestamp(metadata.last_modified),
+        "last_opened": metadata.last_opened.map(timestamp),
+        "version": metadata._version,
+        "pinned": metadata._pinned,
+        "deleted": metadata._deleted,
+        "synced": metadata._synced,
+        "page_count": content.as_ref().map(|content| content.page_count),
+        "orientation": content.as_ref().map(|content| content.orientation.clone()),
+        "size_in_bytes": content.as_ref().and_then(|content| content.size_in_bytes.clone()),
+        "tags": content.map_or_else(Vec::<String>::new, |content| {
+            content.tags.into_iter().map(|tag| tag.name).collect()
+        }),
+    })
+}
+
+fn timestamp(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339()
+}
@@ -0,0 +1,262 @@
+mod backup;
+mod diff;
+mod export;
+mod import;
+mod library;
+mod list;
+mod mount;
+mod query;
+
+use anyhow::Result;
+use clap::{Arg, Command};
+
+fn cli() -> Command<'static> {
+    let format = Arg::new("format")
+        .long("format")
+        .takes_value(true)
+        .possible_values(["text", "json"])
+        .default_value("text")
+        .help("Output format");
+
+    Command::new("rmk")
+        .about("Browse, query, export and mount a reMarkable library")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("root")
+                .long("root")
+                .takes_value(true)
+                .value_name("DIR")
+                .env("RMK_ROOT")
+                .global(true)
+                .help("xochitl folder, a copy of ~/.local/share/remarkable/xochitl"),
+        )
+        .subcommand(
+            Command::new("ls")
+                .about("List the documents of a folder")
+                .arg(Arg::new("folder").help("Folder path or id, the top level by default"))
+                .arg(
+                    Arg::new("long")
+                        .short('l')
+                        .help("Show type, pages, last modification and id"),
+                )
+                .arg(format.clone()),
+        )
+        .subcommand(
+            Command::new("tree")
+                .about("Show the folder tree")
+                .arg(Arg::new("folder").help("Folder path or id, the top level by default"))
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Levels of folders to descend"),
+                )
+                .arg(Arg::new("ids").long("ids").help("Show document ids")),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Show the metadata of a document")
+                .arg(Arg::new("document").required(true).help("Path or id"))
+                .arg(format),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write a document as PDF")
+                .arg(Arg::new("document").required(true).help("Path or id"))
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("File or folder to write to, - for stdout"),
+                ),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Export every document to a folder tree, skipping unchanged ones")
+                .arg(Arg::new("target").required(true).value_name("DIR"))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["pdf", "svg", "png"])
+                        .default_value("pdf")
+                        .help("Format of notebooks, one image per page for svg and png"),
+                )
+                .arg(
+                    Arg::new("scale")
+                        .long("scale")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Size of png pages relative to the screen"),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("Documents rendered in parallel, one per CPU by default"),
+                )
+                .arg(
+                    Arg::new("trash")
+                        .long("trash")
+                        .help("Also export the trash"),
+                )
+                .arg(
+                    Arg::new("keep-removed")
+                        .long("keep-removed")
+                        .help("Keep the files of documents no longer in the library"),
+                ),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Run SQL against the metadata, pages and strokes tables")
+                .arg(
+                    Arg::new("sql")
+                        .required(true)
+                        .help("Statement, - to read stdin"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["table", "csv", "json"])
+                        .default_value("table")
+                        .help("Output format"),
+                ),
+        )
+        .subcommand(
+            Command::new("mount")
+                .about("Mount the library with FUSE until interrupted")
+                .arg(Arg::new("mountpoint").required(true))
+                .arg(
+                    Arg::new("read-write")
+                        .long("read-write")
+                        .help("Write renames, new folders and deletions back"),
+                )
+                .arg(
+                    Arg::new("allow-other")
+                        .long("allow-other")
+                        .help("Let other users access the mount"),
+                )
+                .arg(
+                    Arg::new("umask")
+                        .long("umask")
+                        .takes_value(true)
+                        .default_value("022")
+                        .help("Cleared from the permissions of every file, in octal"),
+                )
+                .arg(
+                    Arg::new("smart-folders")
+                        .long("smart-folders")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .env("RMK_SMART_FOLDERS")
+                        .help("Smart folder definitions shown under /Smart"),
+                )
+                .arg(
+                    Arg::new("snapshots")
+                        .long("snapshots")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .env("RMK_SNAPSHOTS")
+                        .help("Snapshot store shown under /.snapshots"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Add PDF and EPUB files to the library")
+                .arg(
+                    Arg::new("files")
+                        .required(true)
+                        .multiple_values(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("parent")
+                        .long("parent")
+                        .takes_value(true)
+                        .value_name("FOLDER")
+                        .help("Folder path or id, the top level by default"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .takes_value(true)
+                        .help("Name of the document, the file name by default"),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show the pages and strokes changed in a notebook")
+                .arg(Arg::new("document").required(true).help("Path or id"))
+                .arg(
+                    Arg::new("old")
+                        .required(true)
+                        .help("xochitl folder, or snapshot name with --snapshots"),
+                )
+                .arg(Arg::new("new").help("Same as <old>, the root by default"))
+                .arg(
+                    Arg::new("snapshots")
+                        .long("snapshots")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .env("RMK_SNAPSHOTS")
+                        .help("Snapshot store <old> and <new> are taken from"),
+                )
+                .arg(
+                    Arg::new("pdf")
+                        .long("pdf")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Also write the changes highlighted in a PDF"),
+                )
+                .arg(
+                    Arg::new("svg")
+                        .long("svg")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Also write the changes highlighted, one SVG per page"),
+                ),
+        )
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    match cli().get_matches().subcommand() {
+        Some(("ls", args)) => list::ls(args),
+        Some(("tree", args)) => list::tree(args),
+        Some(("info", args)) => list::info(args),
+        Some(("export", args)) => export::export(args),
+        Some(("backup", args)) => backup::backup(args),
+        Some(("query", args)) => query::query(args).await,
+        Some(("mount", args)) => mount::mount(args).await,
+        Some(("import", args)) => import::import(args),
+        Some(("diff", args)) => diff::diff(args),
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cli;
+
+    #[test]
+    fn cli_is_valid() {
+        cli().debug_assert();
+
+        let matches = cli()
+            .try_get_matches_from(["rmk", "ls", "/Work", "-l", "--root", "xochitl"])
+            .unwrap();
+        let (name, args) = matches.subcommand().unwrap();
+        assert_eq!(name, "ls");
+        assert_eq!(args.value_of("root"), Some("xochitl"));
+        assert!(args.is_present("long"));
+    }
+}
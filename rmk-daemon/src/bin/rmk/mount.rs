@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+use futures::stream::StreamExt;
+use log::info;
+use rmk_fs::{MountConfig, RmkFs, SmartFolders, Snapshots};
+use signal_hook::consts::signal::*;
+use signal_hook_tokio::Signals;
+
+use crate::library::root;
+
+/// Mounts the library and serves it until a signal is received.
+pub async fn mount(args: &ArgMatches) -> Result<()> {
+    let mut fs = RmkFs::try_new(&root(args)?)?;
+
+    fs.set_mount_config(MountConfig {
+        read_only: !args.is_present("read-write"),
+        allow_other: args.is_present("allow-other"),
+        umask: u32::from_str_radix(args.value_of("umask").unwrap(), 8)?,
+        ..Default::default()
+    });
+
+    if let Some(config) = args.value_of("smart-folders") {
+        fs.set_smart_folders(SmartFolders::from_file(&PathBuf::from(config))?)?;
+    }
+
+    if let Some(dir) = args.value_of("snapshots") {
+        fs.set_snapshots(Snapshots::open(&PathBuf::from(dir))?);
+    }
+
+    let mut signals = Signals::new([SIGHUP, SIGTERM, SIGINT, SIGQUIT])?;
+    let handle = signals.handle();
+
+    let mount = fs.mount(args.value_of("mountpoint").unwrap())?;
+
+    if let Some(signal) = signals.next().await {
+        info!("Signal: {}", signal);
+    }
+
+    info!("Unmounting");
+    handle.close();
+    mount.join();
+
+    Ok(())
+}
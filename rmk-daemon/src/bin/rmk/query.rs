@@ -0,0 +1,47 @@
+use std::io::{self, Read};
+
+use anyhow::Result;
+use clap::ArgMatches;
+use datafusion::{
+    arrow::{csv, json},
+    prelude::ExecutionContext,
+};
+use rmk_fs::register_tables;
+
+use crate::library::root;
+
+/// Runs the statement against the same tables as `/.rmk/query` in a mount.
+pub async fn query(args: &ArgMatches) -> Result<()> {
+    let mut context = ExecutionContext::new();
+    let table = register_tables(&mut context, &root(args)?)?;
+    table.scan()?;
+
+    let sql = match args.value_of("sql").unwrap() {
+        "-" => {
+            let mut sql = String::new();
+            io::stdin().read_to_string(&mut sql)?;
+            sql
+        }
+        sql => sql.to_string(),
+    };
+
+    let df = context.sql(&sql).await?;
+
+    match args.value_of("format") {
+        Some("csv") => {
+            let mut writer = csv::Writer::new(io::stdout());
+            for batch in df.collect().await? {
+                writer.write(&batch)?;
+            }
+        }
+        Some("json") => {
+            let mut writer = json::ArrayWriter::new(io::stdout());
+            writer.write_batches(&df.collect().await?)?;
+            writer.finish()?;
+            println!();
+        }
+        _ => df.show().await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+//! Read throughput of a mounted RmkFs with concurrent readers, and how long a
+//! directory listing takes while a notebook renders.
+//!
+//! Needs FUSE: `cargo bench -p rmk-fs --bench concurrent_reads`. Skipped when
+//! `/dev/fuse` is missing.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rmk_fs::RmkFs;
+
+const ROUNDS: usize = 20;
+
+fn main() {
+    if !Path::new("/dev/fuse").exists() {
+        eprintln!("/dev/fuse not found, skipping");
+        return;
+    }
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+
+    let mountpoint = std::env::temp_dir().join(format!("rmk-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&mountpoint).unwrap();
+
+    let fs = RmkFs::try_new(&PathBuf::from("../rmk-notebook/samples")).unwrap();
+    let mount = fs.mount(mountpoint.to_str().unwrap()).unwrap();
+
+    let document = std::fs::read_dir(&mountpoint)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "pdf"))
+        .expect("sample notebook is mounted");
+
+    // The first read renders the notebook, list the root meanwhile
+    let rendering = Arc::new(AtomicBool::new(true));
+    let lister = {
+        let rendering = rendering.clone();
+        let mountpoint = mountpoint.clone();
+
+        thread::spawn(move || {
+            let mut slowest = Duration::ZERO;
+            while rendering.load(Ordering::Relaxed) {
+                let start = Instant::now();
+                std::fs::read_dir(&mountpoint).unwrap().count();
+                slowest = slowest.max(start.elapsed());
+            }
+            slowest
+        })
+    };
+
+    let start = Instant::now();
+    let size = std::fs::read(&document).unwrap().len();
+    let render = start.elapsed();
+    rendering.store(false, Ordering::Relaxed);
+
+    println!(
+        "render: {:?} for {} bytes, slowest ls meanwhile: {:?}",
+        render,
+        size,
+        lister.join().unwrap()
+    );
+
+    for readers in [1, 2, 4, 8] {
+        let start = Instant::now();
+
+        let threads: Vec<_> = (0..readers)
+            .map(|_| {
+                let document = document.clone();
+                thread::spawn(move || {
+                    for _ in 0..ROUNDS {
+                        std::fs::read(&document).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let elapsed = start.elapsed();
+        let bytes = (size * readers * ROUNDS) as f64;
+
+        println!(
+            "{} readers: {:.1} MiB/s",
+            readers,
+            bytes / elapsed.as_secs_f64() / (1024.0 * 1024.0)
+        );
+    }
+
+    drop(mount);
+    std::fs::remove_dir(&mountpoint).ok();
+}
@@ -0,0 +1,83 @@
+//! Scan, query and render times over a generated library, 10k documents by
+//! default: `cargo bench -p rmk-fs --bench library [-- <documents>]`.
+
+use std::{sync::Arc, time::Instant};
+
+use datafusion::prelude::ExecutionContext;
+use rmk_fs::{PagesTable, RmkTable};
+use rmk_notebook::{
+    generate::{generate, GeneratorConfig},
+    read_notebook,
+};
+
+const QUERIES: &[&str] = &[
+    "SELECT COUNT(*) FROM metadata",
+    "SELECT id FROM metadata WHERE parent = ''",
+    "SELECT name FROM metadata WHERE pinned AND file_type = 'pdf' ORDER BY last_modified DESC",
+    "SELECT template, COUNT(*) FROM pages GROUP BY template",
+];
+
+const RENDERS: usize = 20;
+
+#[tokio::main]
+async fn main() {
+    let documents = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(10_000);
+
+    let root = std::env::temp_dir().join(format!("rmk-library-{}", std::process::id()));
+    let config = GeneratorConfig::with_documents(documents);
+
+    let start = Instant::now();
+    let library = generate(&root, &config).unwrap();
+    println!("generate {} documents: {:?}", documents, start.elapsed());
+
+    let table = RmkTable::new(&root);
+    let start = Instant::now();
+    table.scan().unwrap();
+    println!("scan: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let report = table.scan().unwrap();
+    assert!(report.is_empty());
+    println!("rescan, nothing changed: {:?}", start.elapsed());
+
+    let mut ctx = ExecutionContext::new();
+    ctx.register_table("metadata", Arc::new(table.clone()))
+        .unwrap();
+    ctx.register_table("pages", Arc::new(PagesTable::new(table.clone())))
+        .unwrap();
+
+    for query in QUERIES {
+        let start = Instant::now();
+        let rows: usize = ctx
+            .clone()
+            .sql(query)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap()
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum();
+        println!("{}: {} rows in {:?}", query, rows, start.elapsed());
+    }
+
+    let start = Instant::now();
+    let mut bytes = 0;
+    for id in library.documents.iter().take(RENDERS.min(config.notebooks)) {
+        let mut pdf = Vec::new();
+        read_notebook(&root, id).unwrap().render(&mut pdf).unwrap();
+        bytes += pdf.len();
+    }
+    println!(
+        "render {} notebooks: {:?}, {} bytes",
+        RENDERS.min(config.notebooks),
+        start.elapsed(),
+        bytes
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
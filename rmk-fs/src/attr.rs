@@ -1,65 +1,155 @@
-use std::time::{Duration, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use fuser::{FileAttr, FileType};
+use rmk_notebook::Metadata;
+
+use crate::config::MountConfig;
 
 pub const TTL: Duration = Duration::from_secs(1); // 1 second
 
 pub const ICON_BYTES: &[u8] = include_bytes!("../resources/.VolumeIcon.icns");
 
-pub const HELLO_DIR_ATTR: FileAttr = FileAttr {
-    ino: 1,
-    size: 0,
-    blocks: 0,
-    atime: UNIX_EPOCH, // 1970-01-01 00:00:00
-    mtime: UNIX_EPOCH,
-    ctime: UNIX_EPOCH,
-    crtime: UNIX_EPOCH,
-    kind: FileType::Directory,
-    perm: 0o755,
-    nlink: 2,
-    uid: 501,
-    gid: 20,
-    rdev: 0,
-    flags: 0,
-    blksize: 512,
-};
-
-pub const HELLO_TXT_CONTENT: &str = "Hello World!\n";
-
-pub const HELLO_TXT_ATTR: FileAttr = FileAttr {
-    ino: 2,
-    size: 13,
-    blocks: 1,
-    atime: UNIX_EPOCH, // 1970-01-01 00:00:00
-    mtime: UNIX_EPOCH,
-    ctime: UNIX_EPOCH,
-    crtime: UNIX_EPOCH,
-    kind: FileType::RegularFile,
-    perm: 0o644,
-    nlink: 1,
-    uid: 501,
-    gid: 20,
-    rdev: 0,
-    flags: 0,
-    blksize: 512,
-};
-
-pub fn volume_icon_attr() -> FileAttr {
+pub const VOLUME_ICON_NAME: &str = ".VolumeIcon.icns";
+
+pub const TRASH_NAME: &str = ".Trash";
+
+const BLOCK_SIZE: u32 = 512;
+
+/// Timestamps of a file or folder in the mount.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Times {
+    pub atime: SystemTime,
+    pub mtime: SystemTime,
+    pub ctime: SystemTime,
+    pub crtime: SystemTime,
+}
+
+impl Times {
+    /// Every timestamp at `time`.
+    pub fn at(time: SystemTime) -> Self {
+        Times {
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+        }
+    }
+
+    /// `lastModified` and `lastOpened` of a document, `changed` being when
+    /// its metadata file was last written.
+    pub fn of(metadata: &Metadata, changed: Option<SystemTime>) -> Self {
+        let mtime = metadata.last_modified;
+        let ctime = changed.unwrap_or(mtime);
+
+        Times {
+            atime: metadata.last_opened.unwrap_or(mtime),
+            mtime,
+            ctime,
+            // Not recorded by xochitl, it can at least not be later
+            crtime: mtime.min(ctime),
+        }
+    }
+}
+
+impl Default for Times {
+    fn default() -> Self {
+        Times::at(UNIX_EPOCH)
+    }
+}
+
+pub fn dir_attr(config: &MountConfig, ino: u64, times: Times) -> FileAttr {
     FileAttr {
-        ino: 3,
-        size: ICON_BYTES.len() as u64,
-        blocks: 1,
-        atime: UNIX_EPOCH, // 1970-01-01 00:00:00
-        mtime: UNIX_EPOCH,
-        ctime: UNIX_EPOCH,
-        crtime: UNIX_EPOCH,
+        ino,
+        size: 0,
+        blocks: 0,
+        atime: times.atime,
+        mtime: times.mtime,
+        ctime: times.ctime,
+        crtime: times.crtime,
+        kind: FileType::Directory,
+        perm: config.dir_perm(),
+        nlink: 2,
+        uid: config.uid,
+        gid: config.gid,
+        rdev: 0,
+        flags: 0,
+        blksize: BLOCK_SIZE,
+    }
+}
+
+pub fn file_attr(config: &MountConfig, ino: u64, size: u64, times: Times) -> FileAttr {
+    FileAttr {
+        ino,
+        size,
+        blocks: blocks(size),
+        atime: times.atime,
+        mtime: times.mtime,
+        ctime: times.ctime,
+        crtime: times.crtime,
         kind: FileType::RegularFile,
-        perm: 0o644,
+        perm: config.file_perm(),
         nlink: 1,
-        uid: 501,
-        gid: 20,
+        uid: config.uid,
+        gid: config.gid,
         rdev: 0,
         flags: 0,
-        blksize: 512,
+        blksize: BLOCK_SIZE,
+    }
+}
+
+pub fn volume_icon_attr(config: &MountConfig) -> FileAttr {
+    file_attr(
+        config,
+        crate::inode::VOLUME_ICON_INO,
+        ICON_BYTES.len() as u64,
+        Times::default(),
+    )
+}
+
+/// 512-byte blocks taken by `size` bytes, as `du` counts them.
+fn blocks(size: u64) -> u64 {
+    size.div_ceil(BLOCK_SIZE as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use rmk_notebook::{Metadata, DOCUMENT_TYPE};
+
+    use super::{blocks, file_attr, Times, BLOCK_SIZE};
+    use crate::config::MountConfig;
+
+    #[test]
+    fn times_of_metadata() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut metadata = Metadata::new(DOCUMENT_TYPE, "", "Notes");
+        metadata.last_modified = modified;
+
+        let times = Times::of(&metadata, None);
+        assert_eq!(times, Times::at(modified));
+
+        let opened = modified + Duration::from_secs(10);
+        let changed = modified - Duration::from_secs(10);
+        metadata.last_opened = Some(opened);
+
+        let times = Times::of(&metadata, Some(changed));
+        assert_eq!(times.atime, opened);
+        assert_eq!(times.mtime, modified);
+        assert_eq!(times.ctime, changed);
+        assert_eq!(times.crtime, changed);
+    }
+
+    #[test]
+    fn blocks_cover_size() {
+        assert_eq!(blocks(0), 0);
+        assert_eq!(blocks(1), 1);
+        assert_eq!(blocks(512), 1);
+        assert_eq!(blocks(513), 2);
+
+        let attr = file_attr(&MountConfig::default(), 42, 10_000, Times::default());
+        assert_eq!(attr.blksize, BLOCK_SIZE);
+        assert!(attr.blocks * attr.blksize as u64 >= attr.size);
+        assert!((attr.blocks - 1) * (attr.blksize as u64) < attr.size);
     }
 }
@@ -0,0 +1,151 @@
+use fuser::MountOption;
+
+/// How RmkFs appears to the rest of the system once mounted.
+#[derive(Clone, Debug)]
+pub struct MountConfig {
+    /// Owner of every file, the user mounting by default
+    pub uid: u32,
+    pub gid: u32,
+    /// Cleared from the `0o777` of folders and the `0o666` of files
+    pub umask: u32,
+    /// Lets other users access the mount, requires `user_allow_other` in
+    /// `/etc/fuse.conf` on Linux
+    pub allow_other: bool,
+    /// Lets root access the mount, exclusive with `allow_other`
+    pub allow_root: bool,
+    /// Renames, new folders and deletions fail with `EROFS`
+    pub read_only: bool,
+    /// Shown in Finder on macOS, ignored elsewhere
+    pub volume_name: String,
+}
+
+impl Default for MountConfig {
+    fn default() -> Self {
+        // SAFETY: getuid and getgid cannot fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+        MountConfig {
+            uid,
+            gid,
+            umask: 0o022,
+            allow_other: false,
+            allow_root: false,
+            read_only: true,
+            volume_name: "Remarkable".to_string(),
+        }
+    }
+}
+
+impl MountConfig {
+    pub fn dir_perm(&self) -> u16 {
+        (0o777 & !self.umask) as u16
+    }
+
+    pub fn file_perm(&self) -> u16 {
+        (0o666 & !self.umask) as u16
+    }
+
+    /// Options passed to fuser. Read-only mode is enforced by each operation
+    /// rather than with `MountOption::RO`, so that `/.rmk/query` stays
+    /// writable.
+    pub fn options(&self) -> Vec<MountOption> {
+        let mut options = vec![
+            MountOption::FSName("remarkable".to_string()),
+            MountOption::Subtype("rmk".to_string()),
+            MountOption::DefaultPermissions,
+        ];
+
+        if self.allow_other {
+            options.push(MountOption::AllowOther);
+        } else if self.allow_root {
+            options.push(MountOption::AllowRoot);
+        }
+
+        // fusermount only cleans up after crashes with one of the above
+        if self.allow_other || self.allow_root {
+            options.push(MountOption::AutoUnmount);
+        }
+
+        options.extend(platform_options(self));
+        options
+    }
+}
+
+/// macFUSE shows a volume name and icon in Finder.
+#[cfg(target_os = "macos")]
+fn platform_options(config: &MountConfig) -> Vec<MountOption> {
+    vec![
+        MountOption::CUSTOM("modules=volname:volicon".to_string()),
+        MountOption::CUSTOM(format!("volname={}", config.volume_name)),
+        MountOption::CUSTOM(format!("iconpath={}", crate::attr::VOLUME_ICON_NAME)),
+    ]
+}
+
+#[cfg(not(target_os = "macos"))]
+fn platform_options(_config: &MountConfig) -> Vec<MountOption> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use fuser::MountOption;
+
+    use super::MountConfig;
+
+    #[test]
+    fn read_only_mounts_are_not_ro() {
+        // The query file stays writable
+        let config = MountConfig::default();
+        assert!(config.read_only);
+        assert!(!config.options().contains(&MountOption::RO));
+    }
+
+    #[test]
+    fn allow_other_and_root() {
+        let config = MountConfig::default();
+        let options = config.options();
+        assert!(options.contains(&MountOption::FSName("remarkable".to_string())));
+        assert!(options.contains(&MountOption::DefaultPermissions));
+        assert!(!options.contains(&MountOption::AutoUnmount));
+
+        // allow_other wins over allow_root
+        let config = MountConfig {
+            allow_other: true,
+            allow_root: true,
+            ..Default::default()
+        };
+        let options = config.options();
+        assert!(options.contains(&MountOption::AllowOther));
+        assert!(!options.contains(&MountOption::AllowRoot));
+        assert!(options.contains(&MountOption::AutoUnmount));
+
+        let config = MountConfig {
+            allow_root: true,
+            ..Default::default()
+        };
+        let options = config.options();
+        assert!(options.contains(&MountOption::AllowRoot));
+        assert!(options.contains(&MountOption::AutoUnmount));
+    }
+
+    #[test]
+    fn permissions_follow_the_umask() {
+        let config = MountConfig::default();
+        assert_eq!(config.dir_perm(), 0o755);
+        assert_eq!(config.file_perm(), 0o644);
+
+        let config = MountConfig {
+            umask: 0o077,
+            ..Default::default()
+        };
+        assert_eq!(config.dir_perm(), 0o700);
+        assert_eq!(config.file_perm(), 0o600);
+
+        let config = MountConfig {
+            umask: 0,
+            ..Default::default()
+        };
+        assert_eq!(config.dir_perm(), 0o777);
+        assert_eq!(config.file_perm(), 0o666);
+    }
+}
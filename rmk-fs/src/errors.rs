@@ -1,7 +1,16 @@
 use std::path::PathBuf;
 
+use datafusion::error::DataFusionError;
+use libc::{
+    c_int, EACCES, EEXIST, EFBIG, EILSEQ, EINVAL, EIO, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, EROFS,
+};
 use thiserror::Error;
 
+#[cfg(target_os = "macos")]
+pub use libc::ENOATTR;
+#[cfg(not(target_os = "macos"))]
+pub use libc::ENODATA as ENOATTR;
+
 #[derive(Error, Debug)]
 pub enum RmkFsError {
     #[error("failed to mount RmkFS at {mountpoint}")]
@@ -13,13 +22,180 @@ pub enum RmkFsError {
     #[error("failed to scan RmkFS at {root}")]
     ScanError { root: PathBuf },
 
+    #[error("invalid configuration {path}: {message}")]
+    ConfigError { path: PathBuf, message: String },
+
     #[error(transparent)]
     NotebookError(#[from] rmk_notebook::Error),
     #[error(transparent)]
-    DataFusionError(#[from] datafusion::error::DataFusionError),
+    DataFusionError(#[from] DataFusionError),
+    #[error(transparent)]
+    ArrowError(#[from] arrow::error::ArrowError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    WatchError(#[from] notify::Error),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("no such entry: {0}")]
+    NotFound(String),
+
+    #[error("entry already exists: {0}")]
+    AlreadyExists(String),
+
+    #[error("not a directory: {0}")]
+    NotADirectory(String),
+
+    #[error("is a directory: {0}")]
+    IsADirectory(String),
+
+    #[error("directory not empty: {0}")]
+    NotEmpty(String),
+
+    #[error("cannot move {id} into itself")]
+    InvalidMove { id: String },
+
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+
+    #[error("no such attribute: {0}")]
+    NoAttribute(String),
+
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("RmkFS is mounted read-only")]
+    ReadOnly,
+
+    #[error("invalid file name: {0}")]
+    InvalidName(String),
+
+    #[error("file too large: {0}")]
+    FileTooLarge(String),
 
-    #[error("fuser error")]
-    FuserError,
+    #[error("task failed: {0}")]
+    TaskFailed(#[from] tokio::task::JoinError),
+}
+
+impl RmkFsError {
+    /// Error number replied to the kernel for this error.
+    pub fn errno(&self) -> c_int {
+        match self {
+            RmkFsError::NotFound(_) => ENOENT,
+            RmkFsError::AlreadyExists(_) => EEXIST,
+            RmkFsError::NotADirectory(_) => ENOTDIR,
+            RmkFsError::IsADirectory(_) => EISDIR,
+            RmkFsError::NotEmpty(_) => ENOTEMPTY,
+            RmkFsError::InvalidMove { .. } | RmkFsError::InvalidQuery(_) => EINVAL,
+            RmkFsError::InvalidName(_) => EILSEQ,
+            RmkFsError::FileTooLarge(_) => EFBIG,
+            RmkFsError::NoAttribute(_) => ENOATTR,
+            RmkFsError::PermissionDenied(_) => EACCES,
+            RmkFsError::ReadOnly => EROFS,
+            RmkFsError::IoError(e) => io_errno(e),
+            RmkFsError::NotebookError(e) => notebook_errno(e),
+            RmkFsError::DataFusionError(e) => datafusion_errno(e),
+            RmkFsError::MountError { .. }
+            | RmkFsError::ScanError { .. }
+            | RmkFsError::ConfigError { .. }
+            | RmkFsError::ArrowError(_)
+            | RmkFsError::WatchError(_)
+            | RmkFsError::JsonError(_)
+            | RmkFsError::TaskFailed(_) => EIO,
+        }
+    }
+}
+
+fn io_errno(error: &std::io::Error) -> c_int {
+    error.raw_os_error().unwrap_or(EIO)
+}
+
+/// Missing files are reported as such, anything unreadable as an I/O error.
+fn notebook_errno(error: &rmk_notebook::Error) -> c_int {
+    match error {
+        rmk_notebook::Error::InvalidFile(e) => io_errno(e),
+        rmk_notebook::Error::InvalidPath(_) => ENOENT,
+        _ => EIO,
+    }
+}
+
+/// Queries that do not parse or plan are the caller's fault.
+fn datafusion_errno(error: &DataFusionError) -> c_int {
+    match error {
+        DataFusionError::IoError(e) => io_errno(e),
+        DataFusionError::SQL(_) | DataFusionError::Plan(_) | DataFusionError::NotImplemented(_) => {
+            EINVAL
+        }
+        _ => EIO,
+    }
 }
 
 pub type RmkFsResult<T> = Result<T, RmkFsError>;
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+
+    use datafusion::error::DataFusionError;
+    use libc::{
+        EACCES, EEXIST, EFBIG, EILSEQ, EINVAL, EIO, EISDIR, ENOENT, ENOSPC, ENOTDIR, ENOTEMPTY,
+        EROFS,
+    };
+
+    use super::{RmkFsError, ENOATTR};
+
+    #[test]
+    fn json_errors_are_io_errors() {
+        let e = serde_json::from_str::<u32>("{").unwrap_err();
+        assert_eq!(RmkFsError::from(e).errno(), EIO);
+    }
+
+    #[test]
+    fn errno() {
+        let name = || "Notes".to_string();
+        for (error, errno) in [
+            (RmkFsError::NotFound(name()), ENOENT),
+            (RmkFsError::AlreadyExists(name()), EEXIST),
+            (RmkFsError::NotADirectory(name()), ENOTDIR),
+            (RmkFsError::IsADirectory(name()), EISDIR),
+            (RmkFsError::NotEmpty(name()), ENOTEMPTY),
+            (RmkFsError::InvalidMove { id: name() }, EINVAL),
+            (RmkFsError::InvalidQuery(name()), EINVAL),
+            (RmkFsError::InvalidName(name()), EILSEQ),
+            (RmkFsError::FileTooLarge(name()), EFBIG),
+            (RmkFsError::NoAttribute(name()), ENOATTR),
+            (RmkFsError::PermissionDenied(name()), EACCES),
+            (RmkFsError::ReadOnly, EROFS),
+            (RmkFsError::ScanError { root: "/".into() }, EIO),
+        ] {
+            assert_eq!(error.errno(), errno, "{}", error);
+        }
+    }
+
+    #[test]
+    fn wrapped_errno() {
+        // OS errors keep their number, others are I/O errors
+        let os = || Error::from_raw_os_error(ENOSPC);
+        assert_eq!(RmkFsError::from(os()).errno(), ENOSPC);
+        let invalid = Error::from(ErrorKind::InvalidData);
+        assert_eq!(RmkFsError::from(invalid).errno(), EIO);
+
+        let notebook = |e: rmk_notebook::Error| RmkFsError::from(e).errno();
+        assert_eq!(notebook(rmk_notebook::Error::InvalidFile(os())), ENOSPC);
+        assert_eq!(
+            notebook(rmk_notebook::Error::InvalidPath(String::new())),
+            ENOENT
+        );
+        assert_eq!(notebook(rmk_notebook::Error::UnknownColor(9)), EIO);
+
+        let datafusion = |e: DataFusionError| RmkFsError::from(e).errno();
+        assert_eq!(datafusion(DataFusionError::IoError(os())), ENOSPC);
+        assert_eq!(datafusion(DataFusionError::Plan(String::new())), EINVAL);
+        assert_eq!(
+            datafusion(DataFusionError::NotImplemented(String::new())),
+            EINVAL
+        );
+        assert_eq!(datafusion(DataFusionError::Internal(String::new())), EIO);
+    }
+}
@@ -0,0 +1,450 @@
+//! Export of a whole library to a folder tree mirroring the one of the
+//! tablet, for backups:
+//!
+//! ```text
+//! <target>/Work/Meeting notes.pdf
+//! <target>/Work/Sketches-001.svg
+//! <target>/.rmk-export.json
+//! ```
+//!
+//! The manifest records the version each document was exported at, so that
+//! running the export again only renders what changed since, and removes
+//! the files of documents that are gone.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use log::{debug, info, warn};
+use rmk_notebook::{read_notebook, Metadata};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{RmkFsError, RmkFsResult},
+    render::render_pdf,
+    table::{RmkTable, ROOT_ID, TRASH_ID},
+};
+
+pub const MANIFEST_NAME: &str = ".rmk-export.json";
+
+const NOTEBOOK_TYPE: &str = "notebook";
+
+/// What documents are exported as. Pages of notebooks are written to one
+/// image each; imported PDFs and EPUBs are always copied as PDF.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Pdf,
+    Svg,
+    Png,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Size of PNG images relative to the screen of the tablet
+    pub scale: f32,
+    /// Documents rendered at the same time
+    pub jobs: usize,
+    /// Also export the trash, to `<target>/trash`
+    pub trash: bool,
+    /// Keep the files of documents removed from the library
+    pub keep_removed: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: ExportFormat::Pdf,
+            scale: 1.,
+            jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+            trash: false,
+            keep_removed: false,
+        }
+    }
+}
+
+/// Documents handled by an [`export`], by id.
+#[derive(Clone, Debug, Default)]
+pub struct ExportReport {
+    pub exported: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl Display for ExportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} exported, {} unchanged, {} removed, {} failed",
+            self.exported.len(),
+            self.unchanged.len(),
+            self.removed.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// A document as last exported.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Exported {
+    path: String,
+    version: usize,
+    last_modified: u64,
+    format: ExportFormat,
+    /// Relative to the target folder
+    files: Vec<String>,
+}
+
+type Manifest = BTreeMap<String, Exported>;
+
+/// A document to export.
+struct Job {
+    id: String,
+    exported: Exported,
+    file_type: Option<String>,
+}
+
+/// Writes every document of `table` to `target`, see the module
+/// documentation. Documents that fail to render are reported, and tried again
+/// on the next export.
+pub fn export(
+    table: &RmkTable,
+    target: &Path,
+    options: &ExportOptions,
+) -> RmkFsResult<ExportReport> {
+    std::fs::create_dir_all(target)?;
+
+    let mut manifest = read_manifest(target)?;
+    let mut report = ExportReport::default();
+
+    let mut folders = vec![ROOT_ID.to_string()];
+    if options.trash {
+        folders.push(TRASH_ID.to_string());
+    }
+
+    let mut jobs = Vec::new();
+    while let Some(folder) = folders.pop() {
+        for entry in table.children(&folder) {
+            let path = table.path(&entry.id).unwrap_or_default();
+
+            if entry.metadata.is_collection() {
+                std::fs::create_dir_all(target.join(relative(&path)))?;
+                folders.push(entry.id);
+                continue;
+            }
+
+            let exported = Exported {
+                version: entry.metadata._version,
+                last_modified: millis(&entry.metadata),
+                format: options.format,
+                files: Vec::new(),
+                path,
+            };
+
+            match manifest.get(&entry.id) {
+                Some(previous) if is_unchanged(previous, &exported, target) => {
+                    report.unchanged.push(entry.id)
+                }
+                _ => jobs.push(Job {
+                    file_type: table.content(&entry.id).ok().and_then(|c| c.file_type),
+                    id: entry.id,
+                    exported,
+                }),
+            }
+        }
+    }
+
+    if options.trash {
+        std::fs::create_dir_all(target.join(TRASH_ID))?;
+    }
+
+    // Documents no longer in the library, or moved to the trash
+    if !options.keep_removed {
+        let listed: HashSet<&String> = jobs
+            .iter()
+            .map(|job| &job.id)
+            .chain(&report.unchanged)
+            .collect();
+        let gone: Vec<String> = manifest
+            .keys()
+            .filter(|id| !listed.contains(id))
+            .cloned()
+            .collect();
+
+        for id in gone {
+            if let Some(previous) = manifest.remove(&id) {
+                remove_files(target, &previous.files, &HashSet::new());
+            }
+            report.removed.push(id);
+        }
+    }
+
+    info!("Exporting {} documents to {}", jobs.len(), target.display());
+
+    let results = render_all(table.root(), target, jobs, options);
+
+    // A document may now be where another one was before
+    let written: HashSet<String> = results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok())
+        .flatten()
+        .cloned()
+        .collect();
+
+    for (job, result) in results {
+        match result {
+            Ok(files) => {
+                if let Some(previous) = manifest.get(&job.id) {
+                    remove_files(target, &previous.files, &written);
+                }
+
+                manifest.insert(
+                    job.id.clone(),
+                    Exported {
+                        files,
+                        ..job.exported
+                    },
+                );
+                report.exported.push(job.id);
+            }
+            Err(e) => {
+                warn!("Failed to export {}: {}", job.exported.path, e);
+                report.failed.push((job.id, e.to_string()));
+            }
+        }
+    }
+
+    write_manifest(target, &manifest)?;
+
+    Ok(report)
+}
+
+/// Renders `jobs` on `options.jobs` threads.
+fn render_all(
+    root: PathBuf,
+    target: &Path,
+    jobs: Vec<Job>,
+    options: &ExportOptions,
+) -> Vec<(Job, RmkFsResult<Vec<String>>)> {
+    let count = jobs.len();
+    let queue = Arc::new(Mutex::new(jobs));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..options.jobs.max(1).min(count))
+        .map(|_| {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            let root = root.clone();
+            let target = target.to_path_buf();
+            let options = options.clone();
+
+            thread::spawn(move || loop {
+                let job = match queue.lock().unwrap().pop() {
+                    Some(job) => job,
+                    None => break,
+                };
+
+                debug!("Exporting {}", job.exported.path);
+                let result = render(&root, &target, &job, &options);
+                if sender.send((job, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let results = receiver.iter().collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    results
+}
+
+/// Writes the files of one document, and returns their paths relative to
+/// `target`.
+fn render(
+    root: &Path,
+    target: &Path,
+    job: &Job,
+    options: &ExportOptions,
+) -> RmkFsResult<Vec<String>> {
+    let base = relative(&job.exported.path);
+
+    if options.format == ExportFormat::Pdf || job.file_type.as_deref() != Some(NOTEBOOK_TYPE) {
+        let file = format!("{}.pdf", base);
+        write_file(&target.join(&file), &render_pdf(root, &job.id)?)?;
+        return Ok(vec![file]);
+    }
+
+    let notebook = read_notebook(root, &job.id)?;
+    let mut files = Vec::with_capacity(notebook.page_count());
+
+    for page in 0..notebook.page_count() {
+        let mut data = Vec::new();
+        let file = match options.format {
+            ExportFormat::Svg => {
+                notebook.render_svg(page, &mut data)?;
+                format!("{}-{:03}.svg", base, page + 1)
+            }
+            _ => {
+                notebook.render_png(page, options.scale, &mut data)?;
+                format!("{}-{:03}.png", base, page + 1)
+            }
+        };
+
+        write_file(&target.join(&file), &data)?;
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+fn is_unchanged(previous: &Exported, current: &Exported, target: &Path) -> bool {
+    previous.path == current.path
+        && previous.version == current.version
+        && previous.last_modified == current.last_modified
+        && previous.format == current.format
+        && previous.files.iter().all(|file| target.join(file).exists())
+}
+
+/// `path` of a document in the library, relative to the target folder.
+fn relative(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+fn millis(metadata: &Metadata) -> u64 {
+    metadata
+        .last_modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64)
+}
+
+/// Writes to a temporary file renamed once complete, so that an interrupted
+/// export never leaves a truncated file behind.
+fn write_file(path: &Path, data: &[u8]) -> RmkFsResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+
+    std::fs::write(&partial, data)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Removes `files` but those in `kept`, and the folders left empty.
+fn remove_files(target: &Path, files: &[String], kept: &HashSet<String>) {
+    for file in files.iter().filter(|file| !kept.contains(file.as_str())) {
+        let path = target.join(file);
+        if let Err(e) = std::fs::remove_file(&path) {
+            debug!("Failed to remove {}: {}", path.display(), e);
+        }
+
+        let mut folder = path.parent();
+        while let Some(dir) = folder.filter(|dir| *dir != target) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+            folder = dir.parent();
+        }
+    }
+}
+
+fn read_manifest(target: &Path) -> RmkFsResult<Manifest> {
+    match std::fs::read_to_string(target.join(MANIFEST_NAME)) {
+        Ok(manifest) => serde_json::from_str(&manifest).map_err(|e| RmkFsError::ConfigError {
+            path: target.join(MANIFEST_NAME),
+            message: e.to_string(),
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_manifest(target: &Path, manifest: &Manifest) -> RmkFsResult<()> {
+    let manifest = serde_json::to_vec_pretty(manifest).map_err(|e| RmkFsError::ConfigError {
+        path: target.join(MANIFEST_NAME),
+        message: e.to_string(),
+    })?;
+
+    write_file(&target.join(MANIFEST_NAME), &manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use rmk_notebook::generate::{generate, GeneratorConfig};
+
+    use super::{export, ExportFormat, ExportOptions};
+    use crate::{errors::RmkFsResult, table::ROOT_ID, RmkTable};
+
+    #[test]
+    fn export_again_and_after_changes() -> RmkFsResult<()> {
+        let dir = std::env::temp_dir().join(format!("rmk-export-{}", std::process::id()));
+        let root = dir.join("xochitl");
+        let target = dir.join("backup");
+        generate(&root, &GeneratorConfig::with_documents(30))?;
+
+        let table = RmkTable::new(&root);
+        table.scan()?;
+
+        let report = export(&table, &target, &ExportOptions::default())?;
+        assert!(report.failed.is_empty());
+        assert!(!report.exported.is_empty());
+
+        let id = report.exported[0].clone();
+        let path = format!("{}.pdf", table.path(&id).unwrap());
+        assert!(target.join(&path[1..]).exists());
+
+        let again = export(&table, &target, &ExportOptions::default())?;
+        assert!(again.exported.is_empty());
+        assert_eq!(again.unchanged.len(), report.exported.len());
+
+        // Renamed documents are exported again, in place of the old file
+        table.move_to(&id, ROOT_ID, "Renamed")?;
+        let renamed = export(&table, &target, &ExportOptions::default())?;
+        assert_eq!(renamed.exported, vec![id.clone()]);
+        assert!(target.join("Renamed.pdf").exists());
+        assert!(!target.join(&path[1..]).exists());
+
+        table.remove(&id)?;
+        let removed = export(&table, &target, &ExportOptions::default())?;
+        assert_eq!(removed.removed, vec![id]);
+        assert!(!target.join("Renamed.pdf").exists());
+
+        let options = ExportOptions {
+            format: ExportFormat::Svg,
+            ..Default::default()
+        };
+        let svg = export(&table, &target, &options)?;
+        assert_eq!(svg.exported.len(), again.unchanged.len() - 1);
+        assert!(walk(&target).iter().any(|file| file.ends_with("-001.svg")));
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    fn walk(dir: &std::path::Path) -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flat_map(|entry| {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    walk(&path)
+                } else {
+                    vec![path.display().to_string()]
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,25 @@
+use datafusion::{
+    logical_plan::{Column, Expr, Operator},
+    scalar::ScalarValue,
+};
+
+/// Value `expr` requires `column` to be equal to, for filters of the form
+/// `column = 'value'` (or `'value' = column`).
+pub fn column_equals(expr: &Expr, column: &str) -> Option<String> {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Column(Column { name, .. }), Expr::Literal(ScalarValue::Utf8(Some(value))))
+            | (Expr::Literal(ScalarValue::Utf8(Some(value))), Expr::Column(Column { name, .. }))
+                if name == column =>
+            {
+                Some(value.clone())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
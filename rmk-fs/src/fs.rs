@@ -1,16 +1,41 @@
-use std::{fmt::Debug, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    ffi::OsStr,
+    fmt::Debug,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
 
-use async_trait::async_trait;
 use datafusion::{error::DataFusionError, prelude::ExecutionContext};
-use fuser::{FileAttr, Filesystem, MountOption};
-use libc::ENOENT;
-use log::info;
+use fuser::{
+    consts::FOPEN_DIRECT_IO, BackgroundSession, FileAttr, FileType, Filesystem, Notifier,
+    ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite,
+    ReplyXattr, Request, TimeOrNow,
+};
+use libc::{c_int, ENOENT, ERANGE};
+use log::{debug, info, warn};
+use rmk_notebook::Metadata;
 use tokio::runtime::Handle;
 
 use crate::{
-    attr::{volume_icon_attr, HELLO_TXT_ATTR, TTL},
-    errors::{RmkFsError, RmkFsResult},
-    table::RmkTable,
+    attr::{
+        dir_attr, file_attr, volume_icon_attr, Times, ICON_BYTES, TRASH_NAME, TTL, VOLUME_ICON_NAME,
+    },
+    config::MountConfig,
+    errors::{RmkFsError, RmkFsResult, ENOATTR},
+    hierarchy::{disambiguate, entry_name, Entry},
+    inode::{
+        Inodes, CONTROL_INO, QUERY_INO, RESULT_CSV_INO, RESULT_JSON_INO, ROOT_INO, SMART_INO,
+        SNAPSHOTS_INO, TRASH_INO, VOLUME_ICON_INO,
+    },
+    query::{QueryFile, ResultFormat, CONTROL_NAME, QUERY_NAME, RESULT_CSV_NAME, RESULT_JSON_NAME},
+    render::RenderCache,
+    smart::{folder_id, folder_name, is_smart, SmartFolders, SMART_ID, SMART_NAME},
+    snapshot::{is_snapshot, snapshot_id, split_id, Snapshots, SNAPSHOTS_ID, SNAPSHOTS_NAME},
+    table::{register_tables, RmkTable, ScanReport, ROOT_ID, TRASH_ID},
+    watch::RootWatcher,
+    xattr::xattrs,
 };
 
 #[derive(Clone)]
@@ -18,55 +43,210 @@ pub struct RmkFs {
     table: Arc<RmkTable>,
     context: ExecutionContext,
     runtime: Handle,
+    inodes: Arc<RwLock<Inodes>>,
+    renders: Arc<RenderCache>,
+    smart: Arc<SmartFolders>,
+    query: Arc<QueryFile>,
+    snapshots: Arc<Snapshots>,
+    config: Arc<MountConfig>,
+}
+
+/// A mounted RmkFs, following changes of its xochitl root.
+pub struct RmkMount {
+    session: BackgroundSession,
+    _watcher: RootWatcher,
+}
+
+impl RmkMount {
+    pub fn join(self) {
+        self.session.join();
+    }
 }
 
 impl RmkFs {
-    pub fn try_new(root: &PathBuf) -> Result<Self, DataFusionError> {
-        let context = ExecutionContext::new();
-        let table = Arc::new(RmkTable::new(root));
+    pub fn try_new(root: &Path) -> Result<Self, DataFusionError> {
+        let mut context = ExecutionContext::new();
+        let table = Arc::new(register_tables(&mut context, root)?);
 
-        let mut fs = RmkFs {
-            table: table.clone(),
+        Ok(RmkFs {
+            table,
             context,
             runtime: Handle::current(),
-        };
+            inodes: Arc::new(RwLock::new(Inodes::new())),
+            renders: Arc::new(RenderCache::default()),
+            smart: Arc::new(SmartFolders::default()),
+            query: Arc::new(QueryFile::default()),
+            snapshots: Arc::new(Snapshots::default()),
+            config: Arc::new(MountConfig::default()),
+        })
+    }
 
-        fs.context.register_table("metadata", table)?;
+    /// RmkFs is mounted read-only unless told otherwise. In read-write mode,
+    /// renames, new folders and deletions are written back to the xochitl
+    /// metadata files.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        Arc::make_mut(&mut self.config).read_only = read_only;
+    }
 
-        Ok(fs)
+    /// Ownership, permissions and mount options, see [`MountConfig`].
+    pub fn set_mount_config(&mut self, config: MountConfig) {
+        self.config = Arc::new(config);
     }
 
-    pub fn mount(self, mountpoint: &str) -> RmkFsResult<fuser::BackgroundSession> {
+    /// Shows `smart` under `/Smart` in the mount, its queries run against the
+    /// tables registered in this RmkFs. Fails if one of them cannot.
+    pub fn set_smart_folders(&mut self, smart: SmartFolders) -> RmkFsResult<()> {
+        smart.validate(&self.context)?;
+        self.smart = Arc::new(smart);
+        Ok(())
+    }
+
+    /// Records a snapshot of the library in `snapshots` whenever it changes,
+    /// and shows them read-only under `/.snapshots/<timestamp>`.
+    pub fn set_snapshots(&mut self, snapshots: Snapshots) {
+        self.snapshots = Arc::new(snapshots);
+    }
+
+    pub fn mount(self, mountpoint: &str) -> RmkFsResult<RmkMount> {
         self.scan()?;
+        self.take_snapshot();
 
-        info!(
-            "Mount point: {:?}",
-            PathBuf::from(mountpoint).canonicalize().unwrap()
-        );
+        let mount_error = |source| RmkFsError::MountError {
+            mountpoint: mountpoint.to_string(),
+            source,
+        };
 
-        let options = &[
-            MountOption::AutoUnmount,
-            MountOption::AllowOther,
-            MountOption::FSName("remarkable".to_string()),
-            MountOption::RO,
-            MountOption::CUSTOM("modules=volname:volicon".to_string()),
-            MountOption::CUSTOM("volname=Remarkable".to_string()),
-            MountOption::CUSTOM("iconpath=.VolumeIcon.icns".to_string()),
-        ];
+        let path = PathBuf::from(mountpoint)
+            .canonicalize()
+            .map_err(mount_error)?;
+        info!("Mount point: {:?}", path);
 
-        fuser::spawn_mount2(self, mountpoint.clone(), options).map_err(|source| {
-            RmkFsError::MountError {
-                mountpoint: mountpoint.to_string(),
-                source,
-            }
+        let options = self.config.options();
+
+        let fs = self.clone();
+        let session = fuser::spawn_mount2(self, mountpoint, &options).map_err(mount_error)?;
+
+        let watcher = RootWatcher::spawn(fs, session.notifier())?;
+
+        Ok(RmkMount {
+            session,
+            _watcher: watcher,
         })
     }
 
-    pub fn scan(&self) -> RmkFsResult<()> {
+    pub fn scan(&self) -> RmkFsResult<ScanReport> {
         self.table.scan()
     }
+
+    pub fn root(&self) -> PathBuf {
+        self.table.root()
+    }
+
+    pub(crate) fn rescan(&self) {
+        if let Err(e) = self.scan() {
+            warn!("Failed to rescan {}: {}", self.root().display(), e);
+        }
+
+        self.renders.clear();
+        self.smart.invalidate();
+        self.query.invalidate();
+        self.take_snapshot();
+    }
+
+    /// Records the current state of the library, if snapshots are enabled.
+    pub(crate) fn take_snapshot(&self) {
+        if let Err(e) = self.snapshots.take(&self.root()) {
+            warn!("Failed to snapshot {}: {}", self.root().display(), e);
+        }
+    }
+
+    /// Picks up on-disk changes of `id` and tells the kernel to drop what it
+    /// cached about it.
+    pub(crate) fn reload(&self, id: &str, notifier: &Notifier) {
+        let (before, after) = match self.table.reload(id) {
+            Ok(versions) => versions,
+            Err(e) => {
+                warn!("Failed to reload {}: {}", id, e);
+                return;
+            }
+        };
+
+        self.renders.invalidate(id);
+        self.smart.invalidate();
+        self.query.invalidate();
+
+        let inodes = self.inodes.read().unwrap();
+
+        // Errors only mean the kernel had nothing cached
+        if let Some(ino) = inodes.get(id) {
+            if let Err(e) = notifier.inval_inode(ino, 0, 0) {
+                debug!("inval_inode {}: {}", ino, e);
+            }
+        }
+
+        for metadata in before.iter().chain(after.iter()) {
+            let folder = if metadata._deleted {
+                TRASH_ID
+            } else {
+                metadata.parent.as_str()
+            };
+
+            if let Some(parent) = inodes.get(folder) {
+                let name = entry_name(id, metadata);
+                if let Err(e) = notifier.inval_entry(parent, OsStr::new(&name)) {
+                    debug!("inval_entry {} {}: {}", parent, name, e);
+                }
+            }
+        }
+    }
+
+    /// Runs `task` on the tokio runtime and replies from there, so the FUSE
+    /// thread is free to take the next request while a document renders.
+    ///
+    /// A panicking task drops `reply`, which fuser answers with `EIO`.
+    pub(crate) fn spawn<F, T, R, S>(&self, op: &'static str, ino: u64, reply: R, task: F, ok: S)
+    where
+        F: Future<Output = RmkFsResult<T>> + Send + 'static,
+        T: Send + 'static,
+        R: ReplyError + Send + 'static,
+        S: FnOnce(R, T) + Send + 'static,
+    {
+        self.runtime.spawn(async move {
+            match task.await {
+                Ok(value) => ok(reply, value),
+                Err(e) => reply.error(errno(op, ino, &e)),
+            }
+        });
+    }
+}
+
+/// The `error` method every fuser reply has, to answer from [`RmkFs::spawn`].
+pub(crate) trait ReplyError {
+    fn error(self, errno: c_int);
+}
+
+macro_rules! reply_error {
+    ($($reply:ty),*) => {
+        $(
+            impl ReplyError for $reply {
+                fn error(self, errno: c_int) {
+                    <$reply>::error(self, errno)
+                }
+            }
+        )*
+    };
 }
 
+reply_error!(
+    ReplyAttr,
+    ReplyData,
+    ReplyDirectory,
+    ReplyEmpty,
+    ReplyEntry,
+    ReplyWrite,
+    ReplyXattr
+);
+
 impl Debug for RmkFs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "RmkFs {{}}")
@@ -74,102 +254,865 @@ impl Debug for RmkFs {
 }
 
 impl Filesystem for RmkFs {
-    fn lookup(
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let name = utf8(name);
+
+        let fs = self.clone();
+        self.spawn(
+            "lookup",
+            parent,
+            reply,
+            async move { fs.lookup_async(parent, name?).await },
+            |reply, (ttl, attr, generation)| reply.entry(&ttl, &attr, generation),
+        );
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        let fs = self.clone();
+        self.spawn(
+            "getattr",
+            ino,
+            reply,
+            async move { fs.getattr_async(ino).await },
+            |reply, (ttl, attr)| reply.attr(&ttl, &attr),
+        );
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        reply: ReplyDirectory,
+    ) {
+        let fs = self.clone();
+        self.spawn(
+            "readdir",
+            ino,
+            reply,
+            async move { fs.readdir_async(ino).await },
+            move |mut reply: ReplyDirectory, entries| {
+                for (next, (ino, kind, name)) in from_offset(entries, offset) {
+                    if reply.add(ino, next, kind, name) {
+                        break;
+                    }
+                }
+                reply.ok();
+            },
+        );
+    }
+
+    fn mkdir(
         &mut self,
-        _req: &fuser::Request<'_>,
+        _req: &Request<'_>,
         parent: u64,
-        name: &std::ffi::OsStr,
-        reply: fuser::ReplyEntry,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
     ) {
-        let name = name.to_str().unwrap().to_owned();
+        let name = utf8(name);
+
+        let fs = self.clone();
+        self.spawn(
+            "mkdir",
+            parent,
+            reply,
+            async move { fs.mkdir_async(parent, name?).await },
+            |reply, (ttl, attr, generation)| reply.entry(&ttl, &attr, generation),
+        );
+    }
 
-        // let fut =
-        let task = self.runtime.spawn(self.lookup_async(parent, name));
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let name = utf8(name);
 
-        match self.runtime.block_on(task) {
-            Ok(Ok((ttl, attr, genetation))) => {
-                reply.entry(&ttl, &attr, genetation);
-            }
-            Err(_) => reply.error(ENOENT),
-        }
+        let fs = self.clone();
+        self.spawn(
+            "unlink",
+            parent,
+            reply,
+            async move { fs.remove_async(parent, name?, false).await },
+            |reply, ()| reply.ok(),
+        );
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let name = utf8(name);
+
+        let fs = self.clone();
+        self.spawn(
+            "rmdir",
+            parent,
+            reply,
+            async move { fs.remove_async(parent, name?, true).await },
+            |reply, ()| reply.ok(),
+        );
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let name = utf8(name);
+        let newname = utf8(newname);
+
+        let fs = self.clone();
+        self.spawn(
+            "rename",
+            parent,
+            reply,
+            async move { fs.rename_async(parent, name?, newparent, newname?).await },
+            |reply, ()| reply.ok(),
+        );
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let fs = self.clone();
+        self.spawn(
+            "setattr",
+            ino,
+            reply,
+            async move { fs.setattr_async(ino, size).await },
+            |reply, (ttl, attr)| reply.attr(&ttl, &attr),
+        );
+    }
+
+    fn open(&mut self, _req: &Request<'_>, _ino: u64, _flags: i32, reply: ReplyOpen) {
+        // Sizes are only known once a document is rendered, read until EOF
+        reply.opened(0, FOPEN_DIRECT_IO);
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let fs = self.clone();
+        self.spawn(
+            "read",
+            ino,
+            reply,
+            async move { fs.read_async(ino, offset, size).await },
+            |reply, data| reply.data(&data),
+        );
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let data = data.to_vec();
+
+        let fs = self.clone();
+        self.spawn(
+            "write",
+            ino,
+            reply,
+            async move { fs.write_async(ino, offset, data).await },
+            |reply, written| reply.written(written as u32),
+        );
+    }
+
+    fn getxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        let name = utf8(name);
+
+        let fs = self.clone();
+        self.spawn(
+            "getxattr",
+            ino,
+            reply,
+            async move { fs.getxattr_async(ino, name?).await },
+            move |reply, value| reply_xattr(reply, size, &value),
+        );
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        let fs = self.clone();
+        self.spawn(
+            "listxattr",
+            ino,
+            reply,
+            async move { fs.listxattr_async(ino).await },
+            move |reply, names| reply_xattr(reply, size, &names),
+        );
     }
 }
 
 impl RmkFs {
-    async fn lookup_async(
+    pub(crate) async fn lookup_async(
         &self,
         parent: u64,
         name: String,
     ) -> RmkFsResult<(Duration, FileAttr, u64)> {
-        if parent == 1 && name == "hello.txt" {
-            Ok((TTL, HELLO_TXT_ATTR, 0))
-        } else if parent == 1 && name == ".VolumeIcon.icns" {
-            Ok((TTL, volume_icon_attr(), 0))
+        if parent == ROOT_INO && name == VOLUME_ICON_NAME {
+            return Ok((TTL, volume_icon_attr(&self.config), 0));
+        }
+
+        if parent == ROOT_INO && name == TRASH_NAME {
+            return Ok((TTL, self.folder_attr(TRASH_INO), 0));
+        }
+
+        if parent == ROOT_INO && name == CONTROL_NAME {
+            return Ok((TTL, self.folder_attr(CONTROL_INO), 0));
+        }
+
+        if parent == CONTROL_INO {
+            let ino = match name.as_str() {
+                QUERY_NAME => QUERY_INO,
+                RESULT_CSV_NAME => RESULT_CSV_INO,
+                RESULT_JSON_NAME => RESULT_JSON_INO,
+                _ => return Err(RmkFsError::NotFound(name)),
+            };
+            return Ok((TTL, self.control_attr(ino), 0));
+        }
+
+        if parent == ROOT_INO && name == SNAPSHOTS_NAME && self.snapshots.is_enabled() {
+            return Ok((TTL, self.folder_attr(SNAPSHOTS_INO), 0));
+        }
+
+        if parent == SNAPSHOTS_INO {
+            if !self.snapshots.contains(&name) {
+                return Err(RmkFsError::NotFound(name));
+            }
+
+            let ino = self
+                .inodes
+                .write()
+                .unwrap()
+                .ino(&snapshot_id(&name, ROOT_ID));
+            return Ok((TTL, self.folder_attr(ino), 0));
+        }
+
+        if parent == ROOT_INO && name == SMART_NAME && !self.smart.is_empty() {
+            return Ok((TTL, self.folder_attr(SMART_INO), 0));
+        }
+
+        if parent == SMART_INO {
+            if !self.smart.contains(&name) {
+                return Err(RmkFsError::NotFound(name));
+            }
+
+            let ino = self.inodes.write().unwrap().ino(&folder_id(&name));
+            return Ok((TTL, self.folder_attr(ino), 0));
+        }
+
+        let parent = self.directory(parent)?;
+        let (id, metadata) = self.child(&parent, &name).await?;
+
+        Ok((TTL, self.attr(&id, &metadata), 0))
+    }
+
+    pub(crate) async fn getattr_async(&self, ino: u64) -> RmkFsResult<(Duration, FileAttr)> {
+        match ino {
+            ROOT_INO | TRASH_INO | CONTROL_INO | SNAPSHOTS_INO => Ok((TTL, self.folder_attr(ino))),
+            VOLUME_ICON_INO => Ok((TTL, volume_icon_attr(&self.config))),
+            QUERY_INO | RESULT_CSV_INO | RESULT_JSON_INO => Ok((TTL, self.control_attr(ino))),
+            _ if self.smart_id(ino).is_some() || self.snapshot_dir(ino).is_some() => {
+                Ok((TTL, self.folder_attr(ino)))
+            }
+            _ => {
+                let (id, metadata) = self.node(ino)?;
+                Ok((TTL, self.attr(&id, &metadata)))
+            }
+        }
+    }
+
+    pub(crate) async fn readdir_async(
+        &self,
+        ino: u64,
+    ) -> RmkFsResult<Vec<(u64, FileType, String)>> {
+        if ino == CONTROL_INO {
+            return Ok(vec![
+                (CONTROL_INO, FileType::Directory, ".".to_string()),
+                (ROOT_INO, FileType::Directory, "..".to_string()),
+                (QUERY_INO, FileType::RegularFile, QUERY_NAME.to_string()),
+                (
+                    RESULT_CSV_INO,
+                    FileType::RegularFile,
+                    RESULT_CSV_NAME.to_string(),
+                ),
+                (
+                    RESULT_JSON_INO,
+                    FileType::RegularFile,
+                    RESULT_JSON_NAME.to_string(),
+                ),
+            ]);
+        }
+
+        let id = self.directory(ino)?;
+
+        let parent_ino = self.parent_ino(&id)?;
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (parent_ino, FileType::Directory, "..".to_string()),
+        ];
+
+        if ino == ROOT_INO {
+            entries.push((
+                VOLUME_ICON_INO,
+                FileType::RegularFile,
+                VOLUME_ICON_NAME.to_string(),
+            ));
+            entries.push((TRASH_INO, FileType::Directory, TRASH_NAME.to_string()));
+            entries.push((CONTROL_INO, FileType::Directory, CONTROL_NAME.to_string()));
+
+            if !self.smart.is_empty() {
+                entries.push((SMART_INO, FileType::Directory, SMART_NAME.to_string()));
+            }
+
+            if self.snapshots.is_enabled() {
+                entries.push((
+                    SNAPSHOTS_INO,
+                    FileType::Directory,
+                    SNAPSHOTS_NAME.to_string(),
+                ));
+            }
+        }
+
+        if ino == SNAPSHOTS_INO {
+            let mut inodes = self.inodes.write().unwrap();
+            for name in self.snapshots.names()? {
+                let ino = inodes.ino(&snapshot_id(&name, ROOT_ID));
+                entries.push((ino, FileType::Directory, name));
+            }
+        }
+
+        if ino == SMART_INO {
+            let mut inodes = self.inodes.write().unwrap();
+            for name in self.smart.names() {
+                let ino = inodes.ino(&folder_id(name));
+                entries.push((ino, FileType::Directory, name.to_string()));
+            }
+        }
+
+        // Offsets handed to the kernel are indexes, entries come sorted
+        for entry in self.entries(&id).await? {
+            let attr = self.attr(&entry.id, &entry.metadata);
+            entries.push((attr.ino, attr.kind, entry.name));
+        }
+
+        Ok(entries)
+    }
+
+    pub(crate) async fn mkdir_async(
+        &self,
+        parent: u64,
+        name: String,
+    ) -> RmkFsResult<(Duration, FileAttr, u64)> {
+        self.check_writable()?;
+
+        let parent = self.directory(parent)?;
+        if parent == TRASH_ID || is_smart(&parent) || is_snapshot(&parent) {
+            return Err(RmkFsError::PermissionDenied(name));
+        }
+
+        let (id, metadata) = self.table.mkdir(&parent, &name)?;
+        self.changed();
+
+        Ok((TTL, self.attr(&id, &metadata), 0))
+    }
+
+    pub(crate) async fn remove_async(
+        &self,
+        parent: u64,
+        name: String,
+        directory: bool,
+    ) -> RmkFsResult<()> {
+        self.check_writable()?;
+
+        let parent = self.directory(parent)?;
+        if is_smart(&parent) || is_snapshot(&parent) {
+            return Err(RmkFsError::PermissionDenied(name));
+        }
+
+        self.table.unlink(&parent, &name, directory)?;
+        self.changed();
+
+        Ok(())
+    }
+
+    pub(crate) async fn rename_async(
+        &self,
+        parent: u64,
+        name: String,
+        new_parent: u64,
+        new_name: String,
+    ) -> RmkFsResult<()> {
+        self.check_writable()?;
+
+        let parent = self.directory(parent)?;
+        let new_parent = self.directory(new_parent)?;
+        if [&parent, &new_parent]
+            .iter()
+            .any(|folder| is_smart(folder) || is_snapshot(folder))
+        {
+            return Err(RmkFsError::PermissionDenied(name));
+        }
+
+        self.table.rename(&parent, &name, &new_parent, &new_name)?;
+        self.changed();
+
+        Ok(())
+    }
+
+    pub(crate) async fn read_async(
+        &self,
+        ino: u64,
+        offset: i64,
+        size: u32,
+    ) -> RmkFsResult<Vec<u8>> {
+        match ino {
+            VOLUME_ICON_INO => return Ok(slice(ICON_BYTES, offset, size).to_vec()),
+            QUERY_INO => return Ok(slice(&self.query.sql(), offset, size).to_vec()),
+            RESULT_CSV_INO | RESULT_JSON_INO => {
+                let result = self.query.result(&self.context, result_format(ino)).await?;
+                return Ok(slice(&result, offset, size).to_vec());
+            }
+            ROOT_INO | TRASH_INO | CONTROL_INO | SNAPSHOTS_INO => {
+                return Err(RmkFsError::IsADirectory(format!("inode {}", ino)));
+            }
+            _ if self.smart_id(ino).is_some() || self.snapshot_dir(ino).is_some() => {
+                return Err(RmkFsError::IsADirectory(format!("inode {}", ino)));
+            }
+            _ => {}
+        }
+
+        let (id, metadata) = self.node(ino)?;
+        if metadata.is_collection() {
+            return Err(RmkFsError::IsADirectory(metadata.visible_name));
+        }
+
+        // Rendering is CPU bound, keep it off the async workers
+        let pdf = match split_id(&id) {
+            Some((name, id)) => {
+                let snapshots = self.snapshots.clone();
+                let (name, id) = (name.to_string(), id.to_string());
+                tokio::task::spawn_blocking(move || snapshots.render(&name, &id)).await??
+            }
+            None => {
+                let (renders, root, id) = (self.renders.clone(), self.root(), id.clone());
+                tokio::task::spawn_blocking(move || renders.get_or_render(&root, &id)).await??
+            }
+        };
+
+        Ok(slice(&pdf, offset, size).to_vec())
+    }
+
+    /// Only the query file can be written to, even on a read-only mount.
+    pub(crate) async fn write_async(
+        &self,
+        ino: u64,
+        offset: i64,
+        data: Vec<u8>,
+    ) -> RmkFsResult<usize> {
+        if ino != QUERY_INO {
+            self.check_writable()?;
+            return Err(RmkFsError::PermissionDenied(format!("inode {}", ino)));
+        }
+
+        self.query.write(offset.max(0) as usize, &data)
+    }
+
+    pub(crate) async fn setattr_async(
+        &self,
+        ino: u64,
+        size: Option<u64>,
+    ) -> RmkFsResult<(Duration, FileAttr)> {
+        if let Some(size) = size {
+            if ino != QUERY_INO {
+                self.check_writable()?;
+                return Err(RmkFsError::PermissionDenied(format!("inode {}", ino)));
+            }
+
+            self.query
+                .truncate(usize::try_from(size).unwrap_or(usize::MAX))?;
+        }
+
+        self.getattr_async(ino).await
+    }
+
+    pub(crate) async fn getxattr_async(&self, ino: u64, name: String) -> RmkFsResult<Vec<u8>> {
+        self.xattrs(ino)?
+            .into_iter()
+            .find(|(attr, _)| *attr == name)
+            .map(|(_, value)| value)
+            .ok_or(RmkFsError::NoAttribute(name))
+    }
+
+    pub(crate) async fn listxattr_async(&self, ino: u64) -> RmkFsResult<Vec<u8>> {
+        let mut names = Vec::new();
+
+        for (name, _) in self.xattrs(ino)? {
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+
+        Ok(names)
+    }
+
+    fn xattrs(&self, ino: u64) -> RmkFsResult<Vec<(String, Vec<u8>)>> {
+        match ino {
+            ROOT_INO | TRASH_INO | VOLUME_ICON_INO | CONTROL_INO | QUERY_INO | RESULT_CSV_INO
+            | RESULT_JSON_INO | SNAPSHOTS_INO => Ok(vec![]),
+            _ if self.smart_id(ino).is_some() || self.snapshot_dir(ino).is_some() => Ok(vec![]),
+            _ => {
+                let (id, metadata) = self.node(ino)?;
+
+                match split_id(&id) {
+                    Some((_, id)) => Ok(xattrs(id, &metadata, None)),
+                    None => {
+                        let content = self.table.content(&id).ok();
+                        Ok(xattrs(&id, &metadata, content.as_ref()))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Drops query results computed before a change made through the mount.
+    fn changed(&self) {
+        self.smart.invalidate();
+        self.query.invalidate();
+    }
+
+    fn check_writable(&self) -> RmkFsResult<()> {
+        if self.config.read_only {
+            Err(RmkFsError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn node(&self, ino: u64) -> RmkFsResult<(String, Metadata)> {
+        let id = self
+            .inodes
+            .read()
+            .unwrap()
+            .id(ino)
+            .ok_or_else(|| RmkFsError::NotFound(format!("inode {}", ino)))?;
+
+        let metadata = match split_id(&id) {
+            Some((name, document)) => self.snapshots.view(name)?.get(document).cloned(),
+            None => self.table.get(&id),
+        };
+        let metadata = metadata.ok_or_else(|| RmkFsError::NotFound(id.clone()))?;
+
+        Ok((id, metadata))
+    }
+
+    /// Id of the folder behind `ino`, the root folder being the empty id.
+    fn directory(&self, ino: u64) -> RmkFsResult<String> {
+        match ino {
+            ROOT_INO => return Ok(ROOT_ID.to_string()),
+            TRASH_INO => return Ok(TRASH_ID.to_string()),
+            SNAPSHOTS_INO => return Ok(SNAPSHOTS_ID.to_string()),
+            _ => {}
+        }
+
+        if let Some(id) = self.smart_id(ino).or_else(|| self.snapshot_dir(ino)) {
+            return Ok(id);
+        }
+
+        let (id, metadata) = self.node(ino)?;
+        if metadata.is_collection() {
+            Ok(id)
         } else {
-            Err(RmkFsError::FuserError)
-        }
-    }
-
-    // fn getattr(&mut self, _req: &fuser::Request<'_>, ino: u64, reply: fuser::ReplyAttr) {
-    //     match ino {
-    //         1 => reply.attr(&TTL, &HELLO_DIR_ATTR),
-    //         2 => reply.attr(&TTL, &HELLO_TXT_ATTR),
-    //         3 => reply.attr(&TTL, &volume_icon_attr()),
-    //         _ => reply.error(ENOENT),
-    //     }
-    // }
-
-    // fn read(
-    //     &mut self,
-    //     _req: &Request,
-    //     ino: u64,
-    //     _fh: u64,
-    //     offset: i64,
-    //     size: u32,
-    //     _flags: i32,
-    //     _lock: Option<u64>,
-    //     reply: ReplyData,
-    // ) {
-    //     let from = offset as usize;
-    //     let to = from + size as usize;
-
-    //     if ino == 2 {
-    //         reply.data(&HELLO_TXT_CONTENT.as_bytes()[from..to]);
-    //     } else if ino == 3 {
-    //         let from = offset as usize;
-    //         let to = from + size as usize;
-    //         reply.data(&ICON_BYTES[from..to])
-    //     } else {
-    //         reply.error(ENOENT);
-    //     }
-    // }
-
-    // fn readdir(
-    //     &mut self,
-    //     _req: &fuser::Request<'_>,
-    //     ino: u64,
-    //     _fh: u64,
-    //     offset: i64,
-    //     mut reply: fuser::ReplyDirectory,
-    // ) {
-    //     if ino != 1 {
-    //         reply.error(ENOENT);
-    //         return;
-    //     }
-
-    //     let entries = vec![
-    //         (1, FileType::Directory, "."),
-    //         (1, FileType::Directory, ".."),
-    //         (2, FileType::RegularFile, "hello.txt"),
-    //         (3, FileType::RegularFile, ".VolumeIcon.icns"),
-    //     ];
-
-    //     for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
-    //         // i + 1 means the index of the next entry
-    //         if reply.add(entry.0, (i + 1) as i64, entry.1, entry.2) {
-    //             break;
-    //         }
-    //     }
-    //     reply.ok();
-    // }
+            Err(RmkFsError::NotADirectory(metadata.visible_name))
+        }
+    }
+
+    /// Id of the smart folder directory or smart folder behind `ino`.
+    fn smart_id(&self, ino: u64) -> Option<String> {
+        self.inodes
+            .read()
+            .unwrap()
+            .id(ino)
+            .filter(|id| is_smart(id))
+    }
+
+    /// Id of the snapshot directory behind `ino`.
+    fn snapshot_dir(&self, ino: u64) -> Option<String> {
+        self.inodes
+            .read()
+            .unwrap()
+            .id(ino)
+            .filter(|id| matches!(split_id(id), Some((_, ROOT_ID))))
+    }
+
+    /// Inode of the folder `id` is listed in.
+    fn parent_ino(&self, id: &str) -> RmkFsResult<u64> {
+        let parent = match split_id(id) {
+            Some((_, ROOT_ID)) => return Ok(SNAPSHOTS_INO),
+            Some((name, document)) => self
+                .snapshots
+                .view(name)?
+                .parent(document)
+                .map(|parent| snapshot_id(name, parent)),
+            None => self.table.parent(id),
+        };
+
+        Ok(match parent {
+            Some(parent) => self.inodes.write().unwrap().ino(&parent),
+            None if folder_name(id).is_some() => SMART_INO,
+            None => ROOT_INO,
+        })
+    }
+
+    /// Documents listed in folder `id`, smart folders and snapshots included.
+    async fn entries(&self, id: &str) -> RmkFsResult<Vec<Entry>> {
+        if id == SMART_ID || id == SNAPSHOTS_ID {
+            return Ok(vec![]);
+        }
+
+        if let Some((name, folder)) = split_id(id) {
+            let view = self.snapshots.view(name)?;
+
+            return Ok(view
+                .children(folder)
+                .iter()
+                .map(|entry| Entry {
+                    id: snapshot_id(name, &entry.id),
+                    ..entry.clone()
+                })
+                .collect());
+        }
+
+        match folder_name(id) {
+            Some(name) => {
+                let ids = self.smart.documents(&self.context, name).await?;
+
+                Ok(disambiguate(
+                    ids.into_iter()
+                        .filter_map(|id| self.table.get(&id).map(|metadata| (id, metadata)))
+                        .collect(),
+                    &[],
+                ))
+            }
+            None => Ok(self.table.children(id)),
+        }
+    }
+
+    async fn child(&self, parent: &str, name: &str) -> RmkFsResult<(String, Metadata)> {
+        self.entries(parent)
+            .await?
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| (entry.id, entry.metadata))
+            .ok_or_else(|| RmkFsError::NotFound(name.to_string()))
+    }
+
+    /// Results are only sized once computed, reads go until EOF anyway.
+    fn control_attr(&self, ino: u64) -> FileAttr {
+        let size = match ino {
+            QUERY_INO => self.query.sql().len(),
+            _ => self.query.result_len(result_format(ino)).unwrap_or(0),
+        };
+
+        file_attr(&self.config, ino, size as u64, self.root_times())
+    }
+
+    fn attr(&self, id: &str, metadata: &Metadata) -> FileAttr {
+        let ino = self.inodes.write().unwrap().ino(id);
+        let times = Times::of(metadata, self.table.changed(id));
+
+        if metadata.is_collection() {
+            dir_attr(&self.config, ino, times)
+        } else {
+            file_attr(&self.config, ino, self.size(id), times)
+        }
+    }
+
+    /// Size of the PDF behind `id`, once known: imported PDFs are read as is,
+    /// notebooks only have a size after their first render.
+    fn size(&self, id: &str) -> u64 {
+        if let Some((name, id)) = split_id(id) {
+            return self.snapshots.size(name, id);
+        }
+
+        if let Some(pdf) = self.renders.get(id) {
+            return pdf.len() as u64;
+        }
+
+        std::fs::metadata(self.root().join(format!("{}.pdf", id)))
+            .map(|metadata| metadata.len())
+            .unwrap_or(0)
+    }
+
+    /// Attributes of the folders RmkFs adds to the library.
+    fn folder_attr(&self, ino: u64) -> FileAttr {
+        dir_attr(&self.config, ino, self.root_times())
+    }
+
+    /// Times of the xochitl root, for the folders and files RmkFs adds.
+    fn root_times(&self) -> Times {
+        std::fs::metadata(self.root())
+            .and_then(|metadata| metadata.modified())
+            .map(Times::at)
+            .unwrap_or_default()
+    }
+}
+
+fn result_format(ino: u64) -> ResultFormat {
+    if ino == RESULT_JSON_INO {
+        ResultFormat::Json
+    } else {
+        ResultFormat::Csv
+    }
+}
+
+fn slice(data: &[u8], offset: i64, size: u32) -> &[u8] {
+    let from = (offset.max(0) as usize).min(data.len());
+    let to = from.saturating_add(size as usize).min(data.len());
+
+    &data[from..to]
+}
+
+/// Entries of a directory listing from `offset` on, each with the offset of
+/// the entry after it, which the kernel passes back to continue the listing.
+pub(crate) fn from_offset<T>(entries: Vec<T>, offset: i64) -> impl Iterator<Item = (i64, T)> {
+    entries
+        .into_iter()
+        .enumerate()
+        .skip(offset.max(0) as usize)
+        .map(|(i, entry)| ((i + 1) as i64, entry))
+}
+
+/// Answer to a getxattr or listxattr of a value into a `size` byte buffer.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Xattr<T> {
+    /// Size of the value, for a size probe (`size == 0`)
+    Size(u32),
+    Data(T),
+}
+
+/// Answers a size probe, or with `value` if it fits the buffer.
+pub(crate) fn xattr(size: u32, value: &[u8]) -> Result<Xattr<&[u8]>, c_int> {
+    if size == 0 {
+        Ok(Xattr::Size(value.len() as u32))
+    } else if value.len() > size as usize {
+        Err(ERANGE)
+    } else {
+        Ok(Xattr::Data(value))
+    }
+}
+
+fn reply_xattr(reply: ReplyXattr, size: u32, value: &[u8]) {
+    match xattr(size, value) {
+        Ok(Xattr::Size(size)) => reply.size(size),
+        Ok(Xattr::Data(data)) => reply.data(data),
+        Err(errno) => reply.error(errno),
+    }
+}
+
+/// Names that are not UTF-8 cannot match a visible name, nor become one.
+pub(crate) fn utf8(name: &OsStr) -> RmkFsResult<String> {
+    name.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| RmkFsError::InvalidName(name.to_string_lossy().into_owned()))
+}
+
+/// Logs a failed `op` on inode `ino` and returns the errno to reply with.
+fn errno(op: &str, ino: u64, error: &RmkFsError) -> c_int {
+    let errno = error.errno();
+
+    // Lookups of missing names and attributes are part of normal operation
+    if errno == ENOENT || errno == ENOATTR {
+        debug!("{} {}: {}", op, ino, error);
+    } else {
+        warn!("{} {}: {}", op, ino, error);
+    }
+
+    errno
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use libc::{EILSEQ, ERANGE};
+
+    use super::{from_offset, utf8, xattr, Xattr};
+
+    #[test]
+    fn non_utf8_names_are_rejected() {
+        assert_eq!(utf8(OsStr::new("Notes.pdf")).unwrap(), "Notes.pdf");
+        assert_eq!(
+            utf8(OsStr::from_bytes(b"caf\xe9")).unwrap_err().errno(),
+            EILSEQ
+        );
+    }
+
+    #[test]
+    fn readdir_offsets() {
+        let entries = vec![".", "..", "a", "b"];
+
+        let listed: Vec<_> = from_offset(entries.clone(), 0).collect();
+        assert_eq!(listed, vec![(1, "."), (2, ".."), (3, "a"), (4, "b")]);
+
+        // Offsets handed out continue the listing after their entry
+        let (next, _) = listed[2];
+        assert_eq!(
+            from_offset(entries.clone(), next).collect::<Vec<_>>(),
+            vec![(4, "b")]
+        );
+        assert_eq!(from_offset(entries.clone(), 4).count(), 0);
+        assert_eq!(from_offset(entries, -1).count(), 4);
+    }
+
+    #[test]
+    fn xattr_sizes() {
+        let value = b"notebook";
+
+        assert_eq!(xattr(0, value), Ok(Xattr::Size(8)));
+        assert_eq!(xattr(0, b""), Ok(Xattr::Size(0)));
+        assert_eq!(xattr(8, value), Ok(Xattr::Data(&value[..])));
+        assert_eq!(xattr(64, value), Ok(Xattr::Data(&value[..])));
+        assert_eq!(xattr(7, value), Err(ERANGE));
+    }
 }
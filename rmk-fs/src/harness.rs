@@ -0,0 +1,587 @@
+//! Drives [`RmkFs`] the way the FUSE session does, without the kernel:
+//! requests go through the same name conversion, handlers, dispatch and
+//! errno mapping, listings and attributes are paged and sized like the
+//! [`fuser::Filesystem`] methods do, and replies are collected by
+//! [`MockReply`] instead of being written to `/dev/fuse`.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use fuser::{FileAttr, FileType};
+use libc::c_int;
+use tokio::sync::oneshot;
+
+use crate::{
+    errors::RmkFsResult,
+    fs::{from_offset, utf8, xattr, ReplyError, RmkFs, Xattr},
+    inode::ROOT_INO,
+};
+
+pub const SAMPLE_ID: &str = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+pub const SAMPLE_NAME: &str = "Hedged shared class.pdf";
+
+/// A reply the way the kernel sees it: a value or an errno.
+struct MockReply<T>(oneshot::Sender<Result<T, c_int>>);
+
+impl<T> ReplyError for MockReply<T> {
+    fn error(self, errno: c_int) {
+        let _ = self.0.send(Err(errno));
+    }
+}
+
+/// RmkFs over a copy of the sample xochitl root, removed on drop.
+pub struct Harness {
+    pub fs: RmkFs,
+    pub root: PathBuf,
+}
+
+impl Harness {
+    /// Needs to run on a tokio runtime, like [`RmkFs::try_new`].
+    pub fn new() -> RmkFsResult<Self> {
+        static FIXTURES: AtomicUsize = AtomicUsize::new(0);
+
+        let root = std::env::temp_dir().join(format!(
+            "rmk-fs-{}-{}",
+            std::process::id(),
+            FIXTURES.fetch_add(1, Ordering::Relaxed)
+        ));
+        copy_dir(Path::new("../rmk-notebook/samples"), &root)?;
+
+        let fs = RmkFs::try_new(&root)?;
+        fs.scan()?;
+
+        Ok(Harness { fs, root })
+    }
+
+    async fn request<F, T>(&self, op: &'static str, ino: u64, task: F) -> Result<T, c_int>
+    where
+        F: std::future::Future<Output = RmkFsResult<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.fs
+            .spawn(op, ino, MockReply(sender), task, |reply, value| {
+                let _ = reply.0.send(Ok(value));
+            });
+
+        // A dropped reply is what fuser answers with EIO
+        receiver.await.unwrap_or(Err(libc::EIO))
+    }
+
+    pub async fn lookup(&self, parent: u64, name: impl AsRef<OsStr>) -> Result<FileAttr, c_int> {
+        let (fs, name) = (self.fs.clone(), utf8(name.as_ref()));
+        self.request("lookup", parent, async move {
+            fs.lookup_async(parent, name?).await
+        })
+        .await
+        .map(|(_, attr, _)| attr)
+    }
+
+    pub async fn getattr(&self, ino: u64) -> Result<FileAttr, c_int> {
+        let fs = self.fs.clone();
+        self.request("getattr", ino, async move { fs.getattr_async(ino).await })
+            .await
+            .map(|(_, attr)| attr)
+    }
+
+    pub async fn readdir(&self, ino: u64) -> Result<Vec<(u64, FileType, String)>, c_int> {
+        let entries = self.readdir_from(ino, 0).await?;
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// Entries from `offset` on, with the offset the kernel would continue
+    /// the listing from after each.
+    pub async fn readdir_from(
+        &self,
+        ino: u64,
+        offset: i64,
+    ) -> Result<Vec<(i64, (u64, FileType, String))>, c_int> {
+        let fs = self.fs.clone();
+        let entries = self
+            .request("readdir", ino, async move { fs.readdir_async(ino).await })
+            .await?;
+
+        Ok(from_offset(entries, offset).collect())
+    }
+
+    pub async fn read(&self, ino: u64, offset: i64, size: u32) -> Result<Vec<u8>, c_int> {
+        let fs = self.fs.clone();
+        self.request("read", ino, async move {
+            fs.read_async(ino, offset, size).await
+        })
+        .await
+    }
+
+    /// Reads `ino` in 128 KiB chunks until EOF, like the kernel does with
+    /// direct I/O.
+    pub async fn read_all(&self, ino: u64) -> Result<Vec<u8>, c_int> {
+        const CHUNK: u32 = 128 * 1024;
+
+        let mut data = Vec::new();
+        loop {
+            let chunk = self.read(ino, data.len() as i64, CHUNK).await?;
+            data.extend_from_slice(&chunk);
+
+            if chunk.len() < CHUNK as usize {
+                return Ok(data);
+            }
+        }
+    }
+
+    pub async fn write(&self, ino: u64, offset: i64, data: &[u8]) -> Result<usize, c_int> {
+        let (fs, data) = (self.fs.clone(), data.to_vec());
+        self.request("write", ino, async move {
+            fs.write_async(ino, offset, data).await
+        })
+        .await
+    }
+
+    pub async fn mkdir(&self, parent: u64, name: &str) -> Result<FileAttr, c_int> {
+        let (fs, name) = (self.fs.clone(), name.to_string());
+        self.request("mkdir", parent, async move {
+            fs.mkdir_async(parent, name).await
+        })
+        .await
+        .map(|(_, attr, _)| attr)
+    }
+
+    pub async fn unlink(&self, parent: u64, name: &str) -> Result<(), c_int> {
+        let (fs, name) = (self.fs.clone(), name.to_string());
+        self.request("unlink", parent, async move {
+            fs.remove_async(parent, name, false).await
+        })
+        .await
+    }
+
+    pub async fn rmdir(&self, parent: u64, name: &str) -> Result<(), c_int> {
+        let (fs, name) = (self.fs.clone(), name.to_string());
+        self.request("rmdir", parent, async move {
+            fs.remove_async(parent, name, true).await
+        })
+        .await
+    }
+
+    pub async fn rename(
+        &self,
+        parent: u64,
+        name: &str,
+        new_parent: u64,
+        new_name: &str,
+    ) -> Result<(), c_int> {
+        let (fs, name, new_name) = (self.fs.clone(), name.to_string(), new_name.to_string());
+        self.request("rename", parent, async move {
+            fs.rename_async(parent, name, new_parent, new_name).await
+        })
+        .await
+    }
+
+    /// Reads attribute `name` the way getxattr(2) callers do: a size probe,
+    /// then a read into a buffer of that size.
+    pub async fn getxattr(&self, ino: u64, name: impl AsRef<OsStr>) -> Result<Vec<u8>, c_int> {
+        let size = match self.getxattr_into(ino, name.as_ref(), 0).await? {
+            Xattr::Size(0) => return Ok(vec![]),
+            Xattr::Size(size) => size,
+            Xattr::Data(_) => return Err(libc::EIO),
+        };
+
+        match self.getxattr_into(ino, name.as_ref(), size).await? {
+            Xattr::Data(value) => Ok(value),
+            Xattr::Size(_) => Err(libc::EIO),
+        }
+    }
+
+    /// Attribute `name` read into a `size` byte buffer.
+    pub async fn getxattr_into(
+        &self,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+    ) -> Result<Xattr<Vec<u8>>, c_int> {
+        let (fs, name) = (self.fs.clone(), utf8(name));
+        let value = self
+            .request("getxattr", ino, async move {
+                fs.getxattr_async(ino, name?).await
+            })
+            .await?;
+
+        match xattr(size, &value)? {
+            Xattr::Size(size) => Ok(Xattr::Size(size)),
+            Xattr::Data(data) => Ok(Xattr::Data(data.to_vec())),
+        }
+    }
+
+    pub async fn listxattr(&self, ino: u64) -> Result<Vec<u8>, c_int> {
+        let fs = self.fs.clone();
+        self.request(
+            "listxattr",
+            ino,
+            async move { fs.listxattr_async(ino).await },
+        )
+        .await
+    }
+
+    pub async fn names(&self, ino: u64) -> Result<Vec<String>, c_int> {
+        Ok(self
+            .readdir(ino)
+            .await?
+            .into_iter()
+            .map(|(_, _, name)| name)
+            .collect())
+    }
+
+    pub async fn root_names(&self) -> Result<Vec<String>, c_int> {
+        self.names(ROOT_INO).await
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+pub fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use fuser::FileType;
+    use libc::{EACCES, EEXIST, EFBIG, EILSEQ, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, ERANGE, EROFS};
+
+    use super::{Harness, SAMPLE_NAME};
+    use crate::{
+        errors::{RmkFsResult, ENOATTR},
+        fs::Xattr,
+        inode::{CONTROL_INO, QUERY_INO, RESULT_CSV_INO, ROOT_INO, SNAPSHOTS_INO, TRASH_INO},
+        query::MAX_QUERY_LEN,
+        RmkTable, SmartFolders, Snapshots,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lookup_getattr_readdir() -> RmkFsResult<()> {
+        let harness = Harness::new()?;
+
+        let names = harness.root_names().await.unwrap();
+        for name in [".", "..", ".Trash", ".rmk", SAMPLE_NAME] {
+            assert!(names.contains(&name.to_string()), "{} in {:?}", name, names);
+        }
+        assert_eq!(harness.names(TRASH_INO).await.unwrap(), vec![".", ".."]);
+
+        // Listings continue after the offset of the last entry read
+        let entries = harness.readdir_from(ROOT_INO, 0).await.unwrap();
+        assert_eq!(entries.len(), names.len());
+        let (next, _) = entries[1];
+        let rest = harness.readdir_from(ROOT_INO, next).await.unwrap();
+        assert_eq!(rest[..], entries[2..]);
+        let (last, _) = entries[entries.len() - 1];
+        assert!(harness
+            .readdir_from(ROOT_INO, last)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let attr = harness.lookup(ROOT_INO, SAMPLE_NAME).await.unwrap();
+        assert_eq!(attr.kind, FileType::RegularFile);
+        assert_eq!(harness.getattr(attr.ino).await.unwrap().ino, attr.ino);
+
+        let table = RmkTable::new(&harness.root);
+        table.scan()?;
+        let metadata = table.get(super::SAMPLE_ID).unwrap();
+        assert_eq!(attr.mtime, metadata.last_modified);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn read_renders_the_notebook() -> RmkFsResult<()> {
+        let harness = Harness::new()?;
+
+        let ino = harness.lookup(ROOT_INO, SAMPLE_NAME).await.unwrap().ino;
+        let pdf = harness.read_all(ino).await.unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+
+        // Sizes are known once rendered
+        assert_eq!(harness.getattr(ino).await.unwrap().size, pdf.len() as u64);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn errors() -> RmkFsResult<()> {
+        let harness = Harness::new()?;
+
+        assert_eq!(
+            harness.lookup(ROOT_INO, "missing.pdf").await.err(),
+            Some(ENOENT)
+        );
+        assert_eq!(harness.getattr(12345).await.err(), Some(ENOENT));
+        assert_eq!(
+            harness
+                .lookup(ROOT_INO, OsStr::from_bytes(b"caf\xe9.pdf"))
+                .await
+                .err(),
+            Some(EILSEQ)
+        );
+
+        let ino = harness.lookup(ROOT_INO, SAMPLE_NAME).await.unwrap().ino;
+        assert_eq!(harness.readdir(ino).await.err(), Some(ENOTDIR));
+        assert_eq!(harness.read(ROOT_INO, 0, 16).await.err(), Some(EISDIR));
+        assert_eq!(harness.read(TRASH_INO, 0, 16).await.err(), Some(EISDIR));
+        assert_eq!(harness.read(CONTROL_INO, 0, 16).await.err(), Some(EISDIR));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn query_file() -> RmkFsResult<()> {
+        let harness = Harness::new()?;
+
+        // Read-only mounts accept queries, up to MAX_QUERY_LEN
+        let sql = b"SELECT name FROM metadata";
+        assert_eq!(harness.write(QUERY_INO, 0, sql).await.unwrap(), sql.len());
+        let offset = (MAX_QUERY_LEN - 1) as i64;
+        assert_eq!(
+            harness.write(QUERY_INO, offset, b"--").await.err(),
+            Some(EFBIG)
+        );
+
+        let csv = String::from_utf8(harness.read_all(RESULT_CSV_INO).await.unwrap()).unwrap();
+        assert_eq!(csv, "name\nHedged shared class\n");
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn mkdir_rename_unlink_rmdir() -> RmkFsResult<()> {
+        let mut harness = Harness::new()?;
+        assert_eq!(harness.mkdir(ROOT_INO, "Work").await.err(), Some(EROFS));
+        harness.fs.set_read_only(false);
+
+        // Only one of concurrent mkdirs of the same name succeeds
+        let (first, second) = tokio::join!(
+            harness.mkdir(ROOT_INO, "Work"),
+            harness.mkdir(ROOT_INO, "Work")
+        );
+        assert!(first.is_ok() != second.is_ok());
+        let work = harness.lookup(ROOT_INO, "Work").await.unwrap();
+        assert_eq!(work.kind, FileType::Directory);
+
+        let before = harness.lookup(ROOT_INO, SAMPLE_NAME).await.unwrap().mtime;
+        harness
+            .rename(ROOT_INO, SAMPLE_NAME, work.ino, "Notes.pdf")
+            .await
+            .unwrap();
+        assert_eq!(
+            harness.lookup(ROOT_INO, SAMPLE_NAME).await.err(),
+            Some(ENOENT)
+        );
+        let notes = harness.lookup(work.ino, "Notes.pdf").await.unwrap();
+        assert!(notes.mtime > before);
+
+        let table = RmkTable::new(&harness.root);
+        table.scan()?;
+        let metadata = table.get(super::SAMPLE_ID).unwrap();
+        assert_eq!(metadata.visible_name, "Notes");
+        assert_eq!(table.path(super::SAMPLE_ID).unwrap(), "/Work/Notes");
+
+        assert_eq!(harness.rmdir(ROOT_INO, "Work").await.err(), Some(ENOTEMPTY));
+        assert_eq!(harness.unlink(ROOT_INO, "Work").await.err(), Some(EISDIR));
+        assert_eq!(
+            harness.rmdir(work.ino, "Notes.pdf").await.err(),
+            Some(ENOTDIR)
+        );
+
+        harness.unlink(work.ino, "Notes.pdf").await.unwrap();
+        assert!(harness
+            .names(TRASH_INO)
+            .await
+            .unwrap()
+            .contains(&"Notes.pdf".to_string()));
+
+        harness.rmdir(ROOT_INO, "Work").await.unwrap();
+        assert_eq!(harness.lookup(ROOT_INO, "Work").await.err(), Some(ENOENT));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn trash_and_restore() -> RmkFsResult<()> {
+        let mut harness = Harness::new()?;
+        harness.fs.set_read_only(false);
+
+        // A real folder named like the trash is listed under another name
+        rmk_notebook::create_collection(&harness.root, "", ".Trash")?;
+        harness.fs.scan()?;
+        let names = harness.root_names().await.unwrap();
+        assert!(names.contains(&".Trash (2)".to_string()), "{:?}", names);
+        assert_eq!(
+            harness.lookup(ROOT_INO, ".Trash").await.unwrap().ino,
+            TRASH_INO
+        );
+        assert_eq!(harness.mkdir(ROOT_INO, ".Trash").await.err(), Some(EEXIST));
+
+        harness.unlink(ROOT_INO, SAMPLE_NAME).await.unwrap();
+        assert_eq!(
+            harness.lookup(ROOT_INO, SAMPLE_NAME).await.err(),
+            Some(ENOENT)
+        );
+        assert_eq!(
+            harness.names(TRASH_INO).await.unwrap(),
+            vec![".", "..", SAMPLE_NAME]
+        );
+        assert_eq!(harness.mkdir(TRASH_INO, "Work").await.err(), Some(EACCES));
+        assert_eq!(
+            harness
+                .rename(ROOT_INO, ".Trash (2)", ROOT_INO, ".Trash")
+                .await
+                .err(),
+            Some(EACCES)
+        );
+
+        // Unlinking from the trash flags the document as deleted, which is
+        // still listed there until xochitl syncs
+        harness.unlink(TRASH_INO, SAMPLE_NAME).await.unwrap();
+        let table = RmkTable::new(&harness.root);
+        table.scan()?;
+        let metadata = table.get(super::SAMPLE_ID).unwrap();
+        assert!(metadata._deleted);
+        assert_eq!(metadata.parent, "trash");
+        assert_eq!(
+            harness.unlink(TRASH_INO, SAMPLE_NAME).await.err(),
+            Some(EACCES)
+        );
+
+        // Moving out of the trash restores it
+        harness
+            .rename(TRASH_INO, SAMPLE_NAME, ROOT_INO, SAMPLE_NAME)
+            .await
+            .unwrap();
+        assert_eq!(harness.names(TRASH_INO).await.unwrap(), vec![".", ".."]);
+        harness.lookup(ROOT_INO, SAMPLE_NAME).await.unwrap();
+
+        table.scan()?;
+        let metadata = table.get(super::SAMPLE_ID).unwrap();
+        assert!(!metadata._deleted);
+        assert_eq!(metadata.parent, "");
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn xattrs() -> RmkFsResult<()> {
+        let harness = Harness::new()?;
+        let ino = harness.lookup(ROOT_INO, SAMPLE_NAME).await.unwrap().ino;
+
+        let value = |name: &'static str| {
+            let harness = &harness;
+            async move {
+                let value = harness.getxattr(ino, name).await.unwrap();
+                String::from_utf8(value).unwrap()
+            }
+        };
+        assert_eq!(value("user.rmk.id").await, super::SAMPLE_ID);
+        assert_eq!(value("user.rmk.version").await, "14");
+        assert_eq!(value("user.rmk.pinned").await, "false");
+        assert_eq!(value("user.rmk.synced").await, "true");
+        assert_eq!(value("user.rmk.page_count").await, "6");
+        assert_eq!(value("user.rmk.file_type").await, "notebook");
+        assert_eq!(value("user.rmk.tags").await, "");
+
+        // Size probes, and buffers too small for the value
+        let id = OsStr::new("user.rmk.id");
+        assert_eq!(
+            harness.getxattr_into(ino, id, 0).await,
+            Ok(Xattr::Size(super::SAMPLE_ID.len() as u32))
+        );
+        assert_eq!(harness.getxattr_into(ino, id, 8).await, Err(ERANGE));
+
+        let names = harness.listxattr(ino).await.unwrap();
+        let names: Vec<&[u8]> = names.split(|byte| *byte == 0).collect();
+        assert!(names.contains(&&b"user.rmk.page_count"[..]));
+
+        assert_eq!(
+            harness.getxattr(ino, "user.rmk.missing").await.err(),
+            Some(ENOATTR)
+        );
+        assert_eq!(harness.listxattr(ROOT_INO).await.unwrap(), b"");
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn smart_folders_follow_changes() -> RmkFsResult<()> {
+        let mut harness = Harness::new()?;
+        harness.fs.set_read_only(false);
+
+        let config = harness.root.with_extension("toml");
+        std::fs::write(
+            &config,
+            "[[folder]]\n\
+             name = \"Documents\"\n\
+             query = \"SELECT id FROM metadata WHERE parent <> 'trash'\"\n",
+        )?;
+        harness
+            .fs
+            .set_smart_folders(SmartFolders::from_file(&config)?)?;
+        std::fs::remove_file(config)?;
+
+        let smart = harness.lookup(ROOT_INO, "Smart").await.unwrap().ino;
+        let documents = harness.lookup(smart, "Documents").await.unwrap().ino;
+        assert!(harness
+            .names(documents)
+            .await
+            .unwrap()
+            .contains(&SAMPLE_NAME.to_string()));
+
+        harness.unlink(ROOT_INO, SAMPLE_NAME).await.unwrap();
+        assert_eq!(harness.names(documents).await.unwrap(), vec![".", ".."]);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn snapshots() -> RmkFsResult<()> {
+        let mut harness = Harness::new()?;
+        let store = harness.root.with_extension("snapshots");
+        harness.fs.set_snapshots(Snapshots::open(&store)?);
+        harness.fs.take_snapshot();
+
+        assert!(harness
+            .root_names()
+            .await
+            .unwrap()
+            .contains(&".snapshots".to_string()));
+
+        let names = harness.names(SNAPSHOTS_INO).await.unwrap();
+        assert_eq!(names.len(), 3);
+
+        let snapshot = harness.lookup(SNAPSHOTS_INO, &names[2]).await.unwrap();
+        assert_eq!(snapshot.kind, FileType::Directory);
+
+        let attr = harness.lookup(snapshot.ino, SAMPLE_NAME).await.unwrap();
+        let pdf = harness.read_all(attr.ino).await.unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+
+        std::fs::remove_dir_all(store)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::{HashMap, HashSet};
+
+use log::warn;
+use rmk_notebook::Metadata;
+
+use crate::{
+    attr::{TRASH_NAME, VOLUME_ICON_NAME},
+    names::{decode, file_name},
+    query::CONTROL_NAME,
+    smart::SMART_NAME,
+    snapshot::SNAPSHOTS_NAME,
+    table::{ROOT_ID, TRASH_ID},
+};
+
+const PDF_EXTENSION: &str = ".pdf";
+
+/// Names of the entries the mount adds to the top level. Documents with the
+/// same name are numbered instead, like duplicates.
+pub const RESERVED_NAMES: [&str; 5] = [
+    VOLUME_ICON_NAME,
+    TRASH_NAME,
+    CONTROL_NAME,
+    SMART_NAME,
+    SNAPSHOTS_NAME,
+];
+
+/// A document or folder as listed in its folder.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub id: String,
+    /// Unique among the entries of the folder
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+/// Inconsistencies of the folder tree, which would otherwise hide documents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// The parent is missing or not a folder, the document is shown at the
+    /// top level
+    Orphan { parent: String },
+    /// The parent chain loops back to the document, which is shown at the top
+    /// level to break the cycle
+    Cycle,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    parent: String,
+    path: String,
+}
+
+/// Folder tree of a xochitl root, with the quirks of the metadata resolved:
+/// deleted documents live in the trash, orphans and cycles are attached to the
+/// top level and duplicate names are numbered (`Notes (2).pdf`).
+#[derive(Debug, Default)]
+pub struct Hierarchy {
+    nodes: HashMap<String, Node>,
+    children: HashMap<String, Vec<Entry>>,
+    issues: Vec<(String, Issue)>,
+}
+
+impl Hierarchy {
+    pub fn build(data: &HashMap<String, Metadata>) -> Self {
+        let mut issues = Vec::new();
+
+        let mut parents: HashMap<&str, &str> = HashMap::with_capacity(data.len());
+        for (id, metadata) in data {
+            let parent = metadata.parent.as_str();
+
+            let parent = if metadata._deleted || parent == TRASH_ID {
+                TRASH_ID
+            } else if parent == ROOT_ID || data.get(parent).is_some_and(Metadata::is_collection) {
+                parent
+            } else {
+                issues.push((
+                    id.clone(),
+                    Issue::Orphan {
+                        parent: parent.to_string(),
+                    },
+                ));
+                ROOT_ID
+            };
+
+            parents.insert(id, parent);
+        }
+
+        // Sorted so that the same document breaks a cycle on every build
+        let mut ids: Vec<&str> = data.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+
+        let mut resolved = HashSet::new();
+        for id in ids {
+            let mut chain = Vec::new();
+            let mut node = id;
+
+            while node != ROOT_ID && node != TRASH_ID && !resolved.contains(node) {
+                if let Some(start) = chain.iter().position(|n| *n == node) {
+                    let cut = *chain[start..].iter().min().unwrap();
+                    parents.insert(cut, ROOT_ID);
+                    issues.push((cut.to_string(), Issue::Cycle));
+                    break;
+                }
+
+                chain.push(node);
+                node = parents[node];
+            }
+
+            resolved.extend(chain);
+        }
+
+        for (id, issue) in &issues {
+            warn!("{}: {:?}", id, issue);
+        }
+
+        let mut folders: HashMap<&str, Vec<(String, Metadata)>> = HashMap::new();
+        for (id, parent) in &parents {
+            folders
+                .entry(*parent)
+                .or_default()
+                .push((id.to_string(), data[*id].clone()));
+        }
+
+        let children: HashMap<String, Vec<Entry>> = folders
+            .into_iter()
+            .map(|(folder, entries)| {
+                let reserved: &[&str] = if folder == ROOT_ID {
+                    &RESERVED_NAMES
+                } else {
+                    &[]
+                };
+                (folder.to_string(), disambiguate(entries, reserved))
+            })
+            .collect();
+
+        let mut nodes = HashMap::with_capacity(data.len());
+        let mut folders = vec![
+            (ROOT_ID.to_string(), String::new()),
+            (TRASH_ID.to_string(), format!("/{}", TRASH_ID)),
+        ];
+
+        while let Some((folder, path)) = folders.pop() {
+            for entry in children.get(&folder).into_iter().flatten() {
+                let path = format!("{}/{}", path, base_name(&entry.metadata, &entry.name));
+
+                nodes.insert(
+                    entry.id.clone(),
+                    Node {
+                        parent: folder.clone(),
+                        path: path.clone(),
+                    },
+                );
+                folders.push((entry.id.clone(), path));
+            }
+        }
+
+        Hierarchy {
+            nodes,
+            children,
+            issues,
+        }
+    }
+
+    /// Entries of folder `id`, sorted by name.
+    pub fn children(&self, id: &str) -> &[Entry] {
+        self.children.get(id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Folder `id` is listed in, which is not always its `parent`.
+    pub fn parent(&self, id: &str) -> Option<&str> {
+        self.nodes.get(id).map(|node| node.parent.as_str())
+    }
+
+    /// Location of `id` in the folder tree, e.g. `/Work/Meeting notes`.
+    pub fn path(&self, id: &str) -> Option<&str> {
+        self.nodes.get(id).map(|node| node.path.as_str())
+    }
+
+    /// Inverse of [`Hierarchy::path`], also accepting the names listed by
+    /// [`Hierarchy::children`]. `/` is the top level and `/trash` the trash.
+    pub fn find(&self, path: &str) -> Option<&str> {
+        let mut id = ROOT_ID;
+
+        for (depth, name) in path.split('/').filter(|name| !name.is_empty()).enumerate() {
+            if depth == 0 && name == TRASH_ID {
+                id = TRASH_ID;
+                continue;
+            }
+
+            id = self
+                .children(id)
+                .iter()
+                .find(|entry| {
+                    entry.name == name || base_name(&entry.metadata, &entry.name) == name
+                })?
+                .id
+                .as_str();
+        }
+
+        Some(id)
+    }
+
+    /// Whether `id` is `node` itself or one of its folders.
+    pub fn is_ancestor(&self, id: &str, node: &str) -> bool {
+        let mut node = node;
+
+        loop {
+            if node == id {
+                return true;
+            }
+
+            match self.parent(node) {
+                Some(parent) => node = parent,
+                None => return false,
+            }
+        }
+    }
+
+    pub fn issues(&self) -> &[(String, Issue)] {
+        &self.issues
+    }
+}
+
+/// Names `entries` listed in the same folder. The copy with the smallest id
+/// keeps the plain name, the others are numbered from 2, as are entries named
+/// like one of `reserved`.
+pub fn disambiguate(mut entries: Vec<(String, Metadata)>, reserved: &[&str]) -> Vec<Entry> {
+    entries.sort_by_cached_key(|(id, metadata)| (entry_name(id, metadata), id.clone()));
+
+    let taken: HashSet<String> = entries
+        .iter()
+        .map(|(id, metadata)| entry_name(id, metadata))
+        .chain(reserved.iter().map(|name| name.to_string()))
+        .collect();
+    let mut used: HashSet<String> = reserved.iter().map(|name| name.to_string()).collect();
+
+    let mut entries: Vec<Entry> = entries
+        .into_iter()
+        .map(|(id, metadata)| {
+            let mut name = entry_name(&id, &metadata);
+
+            if !used.insert(name.clone()) {
+                name = (2..)
+                    .map(|n| numbered_name(&id, &metadata, n))
+                    .find(|name| !taken.contains(name) && !used.contains(name))
+                    .unwrap();
+                used.insert(name.clone());
+            }
+
+            Entry { id, name, metadata }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    entries
+}
+
+fn numbered_name(id: &str, metadata: &Metadata, n: usize) -> String {
+    let name = format!("{} ({})", metadata.visible_name, n);
+
+    file_name(id, &name, extension(metadata))
+}
+
+fn extension(metadata: &Metadata) -> &'static str {
+    if metadata.is_collection() {
+        ""
+    } else {
+        PDF_EXTENSION
+    }
+}
+
+/// Whether `name` is listed by the mount itself in folder `parent`, see
+/// [`RESERVED_NAMES`].
+pub fn is_reserved(parent: &str, name: &str) -> bool {
+    parent == ROOT_ID && RESERVED_NAMES.contains(&name)
+}
+
+/// Name of a document in the mount: folders keep their visible name, other
+/// documents are exposed as the PDF they render to. Characters that are not
+/// valid in file names are escaped, see [`crate::names`].
+pub fn entry_name(id: &str, metadata: &Metadata) -> String {
+    file_name(id, &metadata.visible_name, extension(metadata))
+}
+
+/// `name` without the extension [`entry_name`] adds, still escaped.
+fn base_name<'a>(metadata: &Metadata, name: &'a str) -> &'a str {
+    name.strip_suffix(extension(metadata)).unwrap_or(name)
+}
+
+/// Inverse of [`entry_name`], for names coming from the kernel.
+pub fn visible_name(metadata: &Metadata, name: &str) -> String {
+    decode(base_name(metadata, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rmk_notebook::{Metadata, COLLECTION_TYPE, DOCUMENT_TYPE};
+
+    use super::{is_reserved, Hierarchy, Issue};
+
+    fn library(entries: &[(&str, &str, &str, &str)]) -> HashMap<String, Metadata> {
+        entries
+            .iter()
+            .map(|(id, typ, parent, name)| (id.to_string(), Metadata::new(typ, parent, name)))
+            .collect()
+    }
+
+    #[test]
+    fn paths_orphans_cycles_and_duplicates() {
+        let mut data = library(&[
+            ("work", COLLECTION_TYPE, "", "Work"),
+            ("a", DOCUMENT_TYPE, "work", "Notes"),
+            ("b", DOCUMENT_TYPE, "work", "Notes"),
+            ("c", DOCUMENT_TYPE, "work", "Notes (2)"),
+            ("lost", DOCUMENT_TYPE, "missing", "Lost"),
+            ("x", COLLECTION_TYPE, "y", "X"),
+            ("y", COLLECTION_TYPE, "x", "Y"),
+            ("old", DOCUMENT_TYPE, "work", "Old"),
+            ("slash", DOCUMENT_TYPE, "", "Q1/Q2"),
+        ]);
+        data.get_mut("old").unwrap()._deleted = true;
+
+        let hierarchy = Hierarchy::build(&data);
+
+        let names: Vec<&str> = hierarchy
+            .children("work")
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Notes (2).pdf", "Notes (3).pdf", "Notes.pdf"]);
+        assert_eq!(hierarchy.path("a"), Some("/Work/Notes"));
+        assert_eq!(hierarchy.path("b"), Some("/Work/Notes (3)"));
+
+        assert_eq!(hierarchy.path("slash"), Some("/Q1%2FQ2"));
+        assert_eq!(hierarchy.path("lost"), Some("/Lost"));
+        assert_eq!(hierarchy.path("y"), Some("/X/Y"));
+        assert_eq!(hierarchy.path("old"), Some("/trash/Old"));
+        assert!(hierarchy.is_ancestor("x", "y"));
+
+        assert_eq!(hierarchy.find("/Work/Notes (3)"), Some("b"));
+        assert_eq!(hierarchy.find("Work/Notes.pdf/"), Some("a"));
+        assert_eq!(hierarchy.find("/trash/Old"), Some("old"));
+        assert_eq!(hierarchy.find("/"), Some(""));
+        assert_eq!(hierarchy.find("/Work/Missing"), None);
+
+        assert!(hierarchy.issues().contains(&(
+            "lost".to_string(),
+            Issue::Orphan {
+                parent: "missing".to_string()
+            }
+        )));
+        assert!(hierarchy
+            .issues()
+            .contains(&("x".to_string(), Issue::Cycle)));
+    }
+
+    #[test]
+    fn trash_and_reserved_names() {
+        let mut data = library(&[
+            ("fake", COLLECTION_TYPE, "", ".Trash"),
+            ("nested", COLLECTION_TYPE, "fake", ".Trash"),
+            ("note", DOCUMENT_TYPE, "fake", "Note"),
+            ("trashed", DOCUMENT_TYPE, "trash", "Trashed"),
+            ("deleted", DOCUMENT_TYPE, "fake", "Deleted"),
+        ]);
+        data.get_mut("deleted").unwrap()._deleted = true;
+
+        let hierarchy = Hierarchy::build(&data);
+
+        // The real folder does not hide the trash of the mount
+        let names: Vec<&str> = hierarchy
+            .children("")
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec![".Trash (2)"]);
+        assert_eq!(hierarchy.find("/.Trash (2)/Note.pdf"), Some("note"));
+        assert_eq!(hierarchy.path("nested"), Some("/.Trash (2)/.Trash"));
+
+        let trash: Vec<&str> = hierarchy
+            .children("trash")
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(trash, vec!["deleted", "trashed"]);
+        assert_eq!(hierarchy.parent("deleted"), Some("trash"));
+
+        assert!(is_reserved("", ".Trash"));
+        assert!(!is_reserved("fake", ".Trash"));
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::{
+    smart::SMART_ID,
+    snapshot::SNAPSHOTS_ID,
+    table::{ROOT_ID, TRASH_ID},
+};
+
+pub const ROOT_INO: u64 = 1;
+pub const TRASH_INO: u64 = 2;
+pub const VOLUME_ICON_INO: u64 = 3;
+pub const SMART_INO: u64 = 4;
+pub const CONTROL_INO: u64 = 5;
+pub const QUERY_INO: u64 = 6;
+pub const RESULT_CSV_INO: u64 = 7;
+pub const RESULT_JSON_INO: u64 = 8;
+pub const SNAPSHOTS_INO: u64 = 9;
+
+const FIRST_DOCUMENT_INO: u64 = 16;
+
+/// Stable mapping between xochitl document ids and FUSE inode numbers.
+///
+/// Inodes are handed out lazily the first time the kernel sees a document and
+/// are never reused while the filesystem is mounted.
+#[derive(Debug)]
+pub struct Inodes {
+    ids: HashMap<u64, String>,
+    inos: HashMap<String, u64>,
+    next: u64,
+}
+
+impl Inodes {
+    pub fn new() -> Self {
+        let mut inodes = Inodes {
+            ids: HashMap::new(),
+            inos: HashMap::new(),
+            next: FIRST_DOCUMENT_INO,
+        };
+
+        for (ino, id) in [
+            (ROOT_INO, ROOT_ID),
+            (TRASH_INO, TRASH_ID),
+            (SMART_INO, SMART_ID),
+            (SNAPSHOTS_INO, SNAPSHOTS_ID),
+        ] {
+            inodes.ids.insert(ino, id.to_string());
+            inodes.inos.insert(id.to_string(), ino);
+        }
+
+        inodes
+    }
+
+    pub fn ino(&mut self, id: &str) -> u64 {
+        if let Some(ino) = self.inos.get(id) {
+            return *ino;
+        }
+
+        let ino = self.next;
+        self.next += 1;
+
+        self.ids.insert(ino, id.to_string());
+        self.inos.insert(id.to_string(), ino);
+
+        ino
+    }
+
+    /// Inode of `id` if the kernel has already been told about it.
+    pub fn get(&self, id: &str) -> Option<u64> {
+        self.inos.get(id).copied()
+    }
+
+    pub fn id(&self, ino: u64) -> Option<String> {
+        self.ids.get(&ino).cloned()
+    }
+}
@@ -1,10 +1,34 @@
 mod attr;
+mod config;
 mod datasource;
+mod export;
+mod filter;
 mod fs;
+#[cfg(test)]
+mod harness;
+mod hierarchy;
+mod inode;
+mod names;
+mod pages;
+mod query;
+mod render;
+mod smart;
+mod snapshot;
+mod strokes;
 mod table;
+mod watch;
+mod xattr;
 
 pub mod errors;
 
-pub use fs::RmkFs;
+pub use config::MountConfig;
+pub use export::{export, ExportFormat, ExportOptions, ExportReport};
+pub use fs::{RmkFs, RmkMount};
+pub use hierarchy::{Entry, Issue};
 
-pub use table::RmkTable;
+pub use pages::PagesTable;
+pub use render::render_pdf;
+pub use smart::SmartFolders;
+pub use snapshot::{SnapshotView, Snapshots};
+pub use strokes::StrokesTable;
+pub use table::{register_tables, RmkTable, ScanReport, ROOT_ID, TRASH_ID};
@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+/// Longest file name accepted by FUSE and most filesystems, in bytes.
+pub const NAME_MAX: usize = 255;
+
+/// Length of the id suffix of shortened names, e.g. ` (0d9af7de)`.
+const ID_SUFFIX_LEN: usize = 11;
+
+/// Escapes a visible name into a valid file name. `%`, `/`, control
+/// characters (NUL included) and trailing dots and spaces are percent-encoded,
+/// which also covers `.` and `..`. [`decode`] reverses it.
+pub fn encode(name: &str) -> String {
+    let kept = name.trim_end_matches(['.', ' ']).len();
+    let mut encoded = String::with_capacity(name.len());
+
+    for (i, c) in name.char_indices() {
+        if c == '%' || c == '/' || c.is_control() || i >= kept {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                write!(encoded, "%{:02X}", byte).unwrap();
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+
+    encoded
+}
+
+/// Inverse of [`encode`]. A `%` that does not start an escape is kept as is,
+/// so names typed by users come through unchanged.
+pub fn decode(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(byte) = escaped_byte(bytes, i) {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).unwrap_or_else(|_| name.to_string())
+}
+
+/// File name of document `id` whose visible name is `name`, followed by
+/// `extension`. Names that are empty or too long once escaped are shortened
+/// and suffixed with the start of the id, which keeps them unique and stable.
+pub fn file_name(id: &str, name: &str, extension: &str) -> String {
+    let mut encoded = encode(name);
+
+    if encoded.is_empty() || encoded.len() + extension.len() > NAME_MAX {
+        truncate(&mut encoded, NAME_MAX - extension.len() - ID_SUFFIX_LEN);
+
+        let short_id: String = id.chars().take(8).collect();
+        encoded = format!("{} ({})", encoded, short_id)
+            .trim_start()
+            .to_string();
+    }
+
+    format!("{}{}", encoded, extension)
+}
+
+fn escaped_byte(bytes: &[u8], i: usize) -> Option<u8> {
+    match bytes.get(i..i + 3)? {
+        [b'%', high, low] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+            u8::from_str_radix(std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?, 16).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Cuts an escaped name to at most `len` bytes without splitting a character
+/// or an escape sequence.
+fn truncate(encoded: &mut String, len: usize) {
+    let bytes = encoded.as_bytes();
+
+    let mut end = 0;
+    while end < bytes.len() {
+        let next = if escaped_byte(bytes, end).is_some() {
+            end + 3
+        } else {
+            end + encoded[end..].chars().next().map_or(1, char::len_utf8)
+        };
+
+        if next > len {
+            break;
+        }
+        end = next;
+    }
+
+    encoded.truncate(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, file_name, NAME_MAX};
+
+    #[test]
+    fn escapes_round_trip() {
+        for name in [
+            "Notes",
+            "a/b",
+            "100%",
+            "..",
+            "trailing. ",
+            "nul\0",
+            "Café ☕",
+        ] {
+            let encoded = encode(name);
+
+            assert!(!encoded.contains('/') && !encoded.contains('\0'));
+            assert!(!encoded.ends_with('.') && !encoded.ends_with(' '));
+            assert_eq!(decode(&encoded), name);
+        }
+
+        assert_eq!(encode("a/b"), "a%2Fb");
+        assert_eq!(encode(".."), "%2E%2E");
+        assert_eq!(decode("50% off"), "50% off");
+    }
+
+    #[test]
+    fn shortens_long_and_empty_names() {
+        let id = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+        let long = file_name(id, &"é".repeat(300), ".pdf");
+        assert!(long.len() <= NAME_MAX);
+        assert!(long.ends_with(" (0d9af7de).pdf"));
+
+        assert_eq!(file_name(id, "", ".pdf"), "(0d9af7de).pdf");
+        assert_eq!(file_name(id, "a/b", ""), "a%2Fb");
+    }
+}
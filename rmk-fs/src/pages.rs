@@ -0,0 +1,434 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use arrow::{
+    array::{Float32Builder, ListBuilder, StringBuilder, UInt64Builder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use datafusion::{
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider},
+    error::DataFusionError,
+    logical_plan::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+};
+use log::warn;
+use rmk_notebook::{read_page_with_id, read_pagedata_with_id, BoundingBox, Content, Page};
+
+use crate::{errors::RmkFsResult, filter::column_equals, table::RmkTable};
+
+/// Statistics of a single page, see [`PagesTable`].
+#[derive(Clone, Debug)]
+struct PageRow {
+    page_id: String,
+    template: Option<String>,
+    layer_count: usize,
+    stroke_count: usize,
+    point_count: usize,
+    bounding_box: Option<BoundingBox>,
+    dominant_brush: Option<String>,
+    colors: Vec<String>,
+}
+
+impl PageRow {
+    fn new(page_id: &str, template: Option<String>, page: Option<&Page>) -> Self {
+        let mut brushes = HashMap::new();
+        let mut colors = Vec::new();
+
+        for line in page.iter().flat_map(|page| page.lines()) {
+            *brushes.entry(format!("{:?}", line.brush_type)).or_insert(0) += 1;
+
+            let color = format!("{:?}", line.color);
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+
+        colors.sort();
+
+        PageRow {
+            page_id: page_id.to_string(),
+            template,
+            layer_count: page.map_or(0, |page| page.layers.len()),
+            stroke_count: page.map_or(0, |page| page.lines().count()),
+            point_count: page.map_or(0, |page| page.lines().map(|l| l.points.len()).sum()),
+            bounding_box: page.and_then(Page::bounding_box),
+            // Ties go to the brush coming last alphabetically, to stay stable
+            dominant_brush: brushes
+                .into_iter()
+                .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(a.cmp(b)))
+                .map(|(brush, _)| brush),
+            colors,
+        }
+    }
+}
+
+fn page_rows(root: &Path, id: &str, content: &Content) -> RmkFsResult<Vec<PageRow>> {
+    // Imported PDFs and EPUBs have no templates
+    let templates = read_pagedata_with_id(root, id).unwrap_or_default();
+
+    content
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, page_id)| {
+            let page = read_page_with_id(root, id, page_id)?;
+            Ok(PageRow::new(
+                page_id,
+                templates.get(index).cloned(),
+                page.as_ref(),
+            ))
+        })
+        .collect()
+}
+
+/// Page rows of a document and the version they were read from.
+type CachedRows = (usize, Arc<Vec<PageRow>>);
+
+/// One row per page of every document in a [`RmkTable`], with statistics
+/// computed from its `.rm` file.
+///
+/// Parsing strokes is expensive, so pages are only read again when the
+/// version of their document changes. A `document_id = '...'` filter
+/// restricts which documents are read at all, and a limit stops reading
+/// once enough pages were found.
+#[derive(Clone, Debug)]
+pub struct PagesTable {
+    table: RmkTable,
+    schema: SchemaRef,
+    cache: Arc<Mutex<HashMap<String, CachedRows>>>,
+}
+
+impl PagesTable {
+    pub fn new(table: RmkTable) -> Self {
+        Self {
+            table,
+            schema: SchemaRef::new(Schema::new(vec![
+                Field::new("document_id", DataType::Utf8, false),
+                Field::new("page_id", DataType::Utf8, false),
+                Field::new("page_index", DataType::UInt64, false),
+                Field::new("template", DataType::Utf8, true),
+                Field::new("layer_count", DataType::UInt64, false),
+                Field::new("stroke_count", DataType::UInt64, false),
+                Field::new("point_count", DataType::UInt64, false),
+                Field::new("min_x", DataType::Float32, true),
+                Field::new("min_y", DataType::Float32, true),
+                Field::new("max_x", DataType::Float32, true),
+                Field::new("max_y", DataType::Float32, true),
+                Field::new("dominant_brush", DataType::Utf8, true),
+                Field::new(
+                    "colors",
+                    DataType::List(Box::new(Field::new("item", DataType::Utf8, true))),
+                    false,
+                ),
+            ])),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Page rows of the `wanted` documents, or all if empty, refreshing the
+    /// ones that changed, until at least `limit` pages were found.
+    /// Pages are parsed without holding the cache, so that concurrent
+    /// queries only wait on each other for lookups.
+    fn documents(
+        &self,
+        wanted: &[String],
+        limit: Option<usize>,
+    ) -> Vec<(String, Arc<Vec<PageRow>>)> {
+        let root = self.table.root();
+        let mut documents = self.table.documents();
+
+        {
+            // Documents gone from the table are dropped from the cache
+            let live: HashSet<&String> = documents.iter().map(|(id, _)| id).collect();
+            self.cache.lock().unwrap().retain(|id, _| live.contains(id));
+        }
+
+        documents.retain(|(id, _)| wanted.is_empty() || wanted.contains(id));
+
+        let cached: Vec<Option<Arc<Vec<PageRow>>>> = {
+            let cache = self.cache.lock().unwrap();
+            documents
+                .iter()
+                .map(|(id, metadata)| match cache.get(id) {
+                    Some((version, rows)) if *version == metadata._version => Some(rows.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut rows = Vec::with_capacity(documents.len());
+        let mut refreshed = HashMap::new();
+        let mut count = 0;
+
+        for ((id, metadata), cached) in documents.into_iter().zip(cached) {
+            if limit.is_some_and(|limit| count >= limit) {
+                break;
+            }
+
+            let document_rows = match cached {
+                Some(document_rows) => document_rows,
+                None => {
+                    let document_rows = self
+                        .table
+                        .content(&id)
+                        .and_then(|content| page_rows(&root, &id, &content));
+
+                    let document_rows = match document_rows {
+                        Ok(document_rows) => Arc::new(document_rows),
+                        Err(e) => {
+                            warn!("Skipping pages of {}: {}", id, e);
+                            continue;
+                        }
+                    };
+
+                    refreshed.insert(id.clone(), (metadata._version, document_rows.clone()));
+                    document_rows
+                }
+            };
+
+            count += document_rows.len();
+            rows.push((id, document_rows));
+        }
+
+        self.cache.lock().unwrap().extend(refreshed);
+
+        rows
+    }
+
+    fn record_batch(
+        &self,
+        wanted: &[String],
+        limit: Option<usize>,
+    ) -> Result<RecordBatch, DataFusionError> {
+        let documents = self.documents(wanted, limit);
+        let count = documents.iter().map(|(_, rows)| rows.len()).sum();
+
+        let mut document_id_array = StringBuilder::new(count);
+        let mut page_id_array = StringBuilder::new(count);
+        let mut page_index_array = UInt64Builder::new(count);
+        let mut template_array = StringBuilder::new(count);
+        let mut layer_count_array = UInt64Builder::new(count);
+        let mut stroke_count_array = UInt64Builder::new(count);
+        let mut point_count_array = UInt64Builder::new(count);
+        let mut min_x_array = Float32Builder::new(count);
+        let mut min_y_array = Float32Builder::new(count);
+        let mut max_x_array = Float32Builder::new(count);
+        let mut max_y_array = Float32Builder::new(count);
+        let mut dominant_brush_array = StringBuilder::new(count);
+        let mut colors_array = ListBuilder::new(StringBuilder::new(count));
+
+        for (id, rows) in documents {
+            for (index, row) in rows.iter().enumerate() {
+                document_id_array.append_value(&id)?;
+                page_id_array.append_value(&row.page_id)?;
+                page_index_array.append_value(index as u64)?;
+                template_array.append_option(row.template.as_ref())?;
+                layer_count_array.append_value(row.layer_count as u64)?;
+                stroke_count_array.append_value(row.stroke_count as u64)?;
+                point_count_array.append_value(row.point_count as u64)?;
+                min_x_array.append_option(row.bounding_box.map(|b| b.min_x))?;
+                min_y_array.append_option(row.bounding_box.map(|b| b.min_y))?;
+                max_x_array.append_option(row.bounding_box.map(|b| b.max_x))?;
+                max_y_array.append_option(row.bounding_box.map(|b| b.max_y))?;
+                dominant_brush_array.append_option(row.dominant_brush.as_ref())?;
+
+                for color in &row.colors {
+                    colors_array.values().append_value(color)?;
+                }
+                colors_array.append(true)?;
+            }
+        }
+
+        Ok(RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(document_id_array.finish()),
+                Arc::new(page_id_array.finish()),
+                Arc::new(page_index_array.finish()),
+                Arc::new(template_array.finish()),
+                Arc::new(layer_count_array.finish()),
+                Arc::new(stroke_count_array.finish()),
+                Arc::new(point_count_array.finish()),
+                Arc::new(min_x_array.finish()),
+                Arc::new(min_y_array.finish()),
+                Arc::new(max_x_array.finish()),
+                Arc::new(max_y_array.finish()),
+                Arc::new(dominant_brush_array.finish()),
+                Arc::new(colors_array.finish()),
+            ],
+        )?)
+    }
+}
+
+#[async_trait]
+impl TableProvider for PagesTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        // DataFusion still applies the filter, it only narrows what is parsed
+        match column_equals(filter, "document_id") {
+            Some(_) => Ok(TableProviderFilterPushDown::Inexact),
+            None => Ok(TableProviderFilterPushDown::Unsupported),
+        }
+    }
+
+    async fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let wanted: Vec<String> = filters
+            .iter()
+            .filter_map(|filter| column_equals(filter, "document_id"))
+            .collect();
+
+        // Parsing pages is CPU bound, keep it off the async workers
+        let pages = self.clone();
+        let batch = tokio::task::spawn_blocking(move || pages.record_batch(&wanted, limit))
+            .await
+            .map_err(|e| DataFusionError::Execution(e.to_string()))??;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.clone(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use arrow::array::{Array, Float32Array, StringArray, UInt64Array};
+    use datafusion::{
+        datasource::TableProvider,
+        logical_plan::{col, lit, Expr},
+        physical_plan::collect,
+        prelude::ExecutionContext,
+    };
+    use rmk_notebook::generate::{generate, GeneratorConfig};
+
+    use super::PagesTable;
+    use crate::{errors::RmkFsResult, table::RmkTable};
+
+    const SAMPLE_ID: &str = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+    #[tokio::test]
+    async fn sample_pages() -> RmkFsResult<()> {
+        let table = RmkTable::new(&PathBuf::from("../rmk-notebook/samples"));
+        table.scan()?;
+
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("pages", Arc::new(PagesTable::new(table)))?;
+
+        let query = format!(
+            "SELECT template, stroke_count, min_x, min_y, max_x, max_y FROM pages \
+             WHERE document_id = '{}' ORDER BY page_index",
+            SAMPLE_ID
+        );
+        let batches = ctx.sql(&query).await?.collect().await?;
+        let batch = batches.iter().find(|batch| batch.num_rows() > 0).unwrap();
+        assert_eq!(batch.num_rows(), 6);
+
+        let templates = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!((0..templates.len()).all(|i| templates.value(i) == "P Lined heading"));
+
+        let strokes = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(strokes.values(), &[226, 279, 50, 123, 139, 22]);
+
+        let bounds = |row: usize| {
+            (2..6)
+                .map(|column| {
+                    batch
+                        .column(column)
+                        .as_any()
+                        .downcast_ref::<Float32Array>()
+                        .unwrap()
+                        .value(row)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bounds(2), [43.28134, 97.36501, 1336.709, 453.01218]);
+        assert_eq!(bounds(5), [189.63275, 109.25165, 632.8388, 610.7623]);
+
+        Ok(())
+    }
+
+    /// `document_id` of the rows `pages` returns for a scan.
+    async fn document_ids(
+        pages: &PagesTable,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> RmkFsResult<Vec<String>> {
+        let plan = TableProvider::scan(pages, &Some(vec![0]), filters, limit).await?;
+        let batches = collect(plan, ExecutionContext::new().runtime_env()).await?;
+
+        Ok(batches
+            .iter()
+            .flat_map(|batch| {
+                let ids = batch.column(0);
+                let ids = ids.as_any().downcast_ref::<StringArray>().unwrap();
+                (0..ids.len())
+                    .map(|i| ids.value(i).to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn document_id_and_limit_pushdown() -> RmkFsResult<()> {
+        let root = std::env::temp_dir().join(format!("rmk-pages-{}", std::process::id()));
+        let library = generate(&root, &GeneratorConfig::default())?;
+
+        let table = RmkTable::new(&root);
+        table.scan()?;
+        let pages = PagesTable::new(table);
+
+        let all = document_ids(&pages, &[], None).await?;
+        let id = library
+            .documents
+            .iter()
+            .find(|id| all.contains(id))
+            .unwrap();
+
+        // Only the filtered document is read
+        let filtered =
+            document_ids(&pages, &[col("document_id").eq(lit(id.as_str()))], None).await?;
+        assert!(!filtered.is_empty());
+        assert!(filtered.iter().all(|document_id| document_id == id));
+
+        // Reading stops after the first document with enough pages
+        let limited = document_ids(&pages, &[], Some(1)).await?;
+        assert!(!limited.is_empty());
+        assert!(limited.iter().all(|document_id| *document_id == limited[0]));
+        assert!(limited.len() < all.len());
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
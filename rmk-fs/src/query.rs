@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use arrow::{csv, json, record_batch::RecordBatch};
+use datafusion::prelude::ExecutionContext;
+use log::debug;
+
+use crate::errors::{RmkFsError, RmkFsResult};
+
+/// Name of the control directory in the mount root.
+pub const CONTROL_NAME: &str = ".rmk";
+
+pub const QUERY_NAME: &str = "query";
+pub const RESULT_CSV_NAME: &str = "result.csv";
+pub const RESULT_JSON_NAME: &str = "result.json";
+
+/// Longest statement the query file holds, larger writes fail with `EFBIG`.
+pub const MAX_QUERY_LEN: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResultFormat {
+    Csv,
+    Json,
+}
+
+/// The `/.rmk/query` control file: the SQL statement written to it is run
+/// against the registered tables when one of the result files is read.
+/// Queries do not change the library, so read-only mounts accept them too.
+///
+/// ```sh
+/// echo "SELECT name FROM metadata WHERE pinned" > /mnt/.rmk/query
+/// cat /mnt/.rmk/result.csv
+/// ```
+#[derive(Debug, Default)]
+pub struct QueryFile {
+    sql: Mutex<Vec<u8>>,
+    results: Mutex<HashMap<ResultFormat, Arc<Vec<u8>>>>,
+}
+
+impl QueryFile {
+    pub fn sql(&self) -> Vec<u8> {
+        self.sql.lock().unwrap().clone()
+    }
+
+    pub fn write(&self, offset: usize, data: &[u8]) -> RmkFsResult<usize> {
+        let mut sql = self.sql.lock().unwrap();
+
+        let end = offset.saturating_add(data.len());
+        check_len(end)?;
+        if sql.len() < end {
+            sql.resize(end, b' ');
+        }
+        sql[offset..end].copy_from_slice(data);
+
+        self.invalidate();
+        Ok(data.len())
+    }
+
+    pub fn truncate(&self, size: usize) -> RmkFsResult<()> {
+        check_len(size)?;
+        self.sql.lock().unwrap().resize(size, b' ');
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Size of the result in `format` if it was already computed.
+    pub fn result_len(&self, format: ResultFormat) -> Option<usize> {
+        self.results
+            .lock()
+            .unwrap()
+            .get(&format)
+            .map(|result| result.len())
+    }
+
+    pub async fn result(
+        &self,
+        context: &ExecutionContext,
+        format: ResultFormat,
+    ) -> RmkFsResult<Arc<Vec<u8>>> {
+        let cached = self.results.lock().unwrap().get(&format).cloned();
+        if let Some(result) = cached {
+            return Ok(result);
+        }
+
+        let sql = String::from_utf8(self.sql())
+            .map_err(|_| RmkFsError::InvalidQuery("not valid UTF-8".to_string()))?;
+        let sql = sql.trim();
+
+        let batches = if sql.is_empty() {
+            vec![]
+        } else {
+            debug!("Query: {}", sql);
+            context.clone().sql(sql).await?.collect().await?
+        };
+
+        let result = Arc::new(encode(&batches, format)?);
+        self.results.lock().unwrap().insert(format, result.clone());
+
+        Ok(result)
+    }
+
+    /// Drops computed results, e.g. after the library changed.
+    pub fn invalidate(&self) {
+        self.results.lock().unwrap().clear();
+    }
+}
+
+fn check_len(len: usize) -> RmkFsResult<()> {
+    if len > MAX_QUERY_LEN {
+        Err(RmkFsError::FileTooLarge(QUERY_NAME.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn encode(batches: &[RecordBatch], format: ResultFormat) -> RmkFsResult<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    match format {
+        ResultFormat::Csv => {
+            let mut writer = csv::Writer::new(&mut buffer);
+            for batch in batches {
+                writer.write(batch)?;
+            }
+        }
+        ResultFormat::Json => {
+            let mut writer = json::ArrayWriter::new(&mut buffer);
+            writer.write_batches(batches)?;
+            writer.finish()?;
+        }
+    }
+
+    Ok(buffer)
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use log::info;
+use rmk_notebook::read_notebook;
+
+use crate::errors::RmkFsResult;
+
+/// Rendered documents, kept until their files change on disk.
+#[derive(Debug, Default)]
+pub struct RenderCache {
+    pdfs: Mutex<HashMap<String, Arc<Vec<u8>>>>,
+    /// Held while a document renders, concurrent reads wait for that render
+    /// instead of starting their own
+    rendering: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl RenderCache {
+    /// PDF bytes for `id`: the original file for imported PDFs, a rendering
+    /// of the strokes for notebooks.
+    pub fn get_or_render(&self, root: &Path, id: &str) -> RmkFsResult<Arc<Vec<u8>>> {
+        if let Some(pdf) = self.get(id) {
+            return Ok(pdf);
+        }
+
+        let lock = self
+            .rendering
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_default()
+            .clone();
+        let _rendering = lock.lock().unwrap();
+
+        if let Some(pdf) = self.get(id) {
+            return Ok(pdf);
+        }
+
+        let result = render_pdf(root, id).map(Arc::new);
+        if let Ok(pdf) = &result {
+            self.pdfs
+                .lock()
+                .unwrap()
+                .insert(id.to_string(), pdf.clone());
+        }
+        // Failed renders are retried by the next read
+        self.rendering.lock().unwrap().remove(id);
+
+        result
+    }
+
+    /// PDF bytes for `id` if already rendered.
+    pub fn get(&self, id: &str) -> Option<Arc<Vec<u8>>> {
+        self.pdfs.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn invalidate(&self, id: &str) {
+        self.pdfs.lock().unwrap().remove(id);
+    }
+
+    pub fn clear(&self) {
+        self.pdfs.lock().unwrap().clear();
+    }
+}
+
+/// PDF bytes for `id`, uncached, see [`RenderCache::get_or_render`].
+pub fn render_pdf(root: &Path, id: &str) -> RmkFsResult<Vec<u8>> {
+    let original = root.join(format!("{}.pdf", id));
+
+    if original.exists() {
+        return Ok(std::fs::read(original)?);
+    }
+
+    info!("Rendering {}", id);
+
+    let mut pdf = Vec::new();
+    read_notebook(root, id)?.render(&mut pdf)?;
+    Ok(pdf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::RenderCache;
+
+    #[test]
+    fn failed_render_is_released() {
+        let cache = RenderCache::default();
+        let root = PathBuf::from("../rmk-notebook/samples");
+
+        assert!(cache.get_or_render(&root, "missing").is_err());
+        assert!(cache.rendering.lock().unwrap().is_empty());
+        assert!(cache.get("missing").is_none());
+    }
+}
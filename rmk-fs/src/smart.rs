@@ -0,0 +1,300 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Mutex,
+};
+
+use arrow::{array::StringArray, datatypes::DataType};
+use datafusion::prelude::ExecutionContext;
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::{
+    errors::{RmkFsError, RmkFsResult},
+    names::{encode, NAME_MAX},
+};
+
+/// Name of the directory holding the smart folders in the mount root.
+pub const SMART_NAME: &str = "Smart";
+
+/// Synthetic id of that directory, smart folders are `<SMART_ID>/<name>`.
+pub const SMART_ID: &str = ".smart";
+
+#[derive(Clone, Debug, Deserialize)]
+struct SmartFolder {
+    name: String,
+    query: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SmartConfig {
+    #[serde(default, rename = "folder")]
+    folders: Vec<SmartFolder>,
+}
+
+/// Directories whose content is the result of a SQL query against the tables
+/// registered in RmkFs, e.g.
+///
+/// ```toml
+/// [[folder]]
+/// name = "Pinned"
+/// query = "SELECT id FROM metadata WHERE pinned"
+/// ```
+///
+/// Names are listed as is, so they must be distinct file names that need no
+/// escaping. Queries must return an `id` column. Results are kept until the
+/// next rescan of the xochitl root.
+#[derive(Debug, Default)]
+pub struct SmartFolders {
+    folders: Vec<SmartFolder>,
+    results: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl SmartFolders {
+    pub fn from_file(path: &Path) -> RmkFsResult<Self> {
+        let config = std::fs::read_to_string(path)?;
+        let config: SmartConfig = toml::from_str(&config).map_err(|e| RmkFsError::ConfigError {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+
+        info!(
+            "Loaded {} smart folders from {}",
+            config.folders.len(),
+            path.display()
+        );
+
+        Ok(SmartFolders {
+            folders: config.folders,
+            results: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folders.is_empty()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.folders.iter().map(|folder| folder.name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names().any(|n| n == name)
+    }
+
+    /// Checks every name and plans every query against `context`, so that
+    /// an unusable name, or a query that does not parse or has no text `id`
+    /// column, is reported before being listed.
+    pub fn validate(&self, context: &ExecutionContext) -> RmkFsResult<()> {
+        let mut names = HashSet::new();
+
+        for folder in &self.folders {
+            let name = &folder.name;
+            if name.is_empty() || name.len() > NAME_MAX || encode(name) != *name {
+                return Err(RmkFsError::InvalidName(format!("smart folder {:?}", name)));
+            }
+            if !names.insert(name) {
+                return Err(RmkFsError::InvalidName(format!(
+                    "duplicate smart folder {:?}",
+                    name
+                )));
+            }
+
+            let plan = context
+                .create_logical_plan(&folder.query)
+                .map_err(|e| RmkFsError::InvalidQuery(format!("{}: {}", folder.name, e)))?;
+
+            match plan.schema().field_with_unqualified_name("id") {
+                Ok(field) if *field.data_type() == DataType::Utf8 => {}
+                _ => return Err(no_id_column(&folder.name)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ids of the documents in smart folder `name`.
+    pub async fn documents(
+        &self,
+        context: &ExecutionContext,
+        name: &str,
+    ) -> RmkFsResult<Vec<String>> {
+        let cached = self.results.lock().unwrap().get(name).cloned();
+        if let Some(ids) = cached {
+            return Ok(ids);
+        }
+
+        let folder = self
+            .folders
+            .iter()
+            .find(|folder| folder.name == name)
+            .ok_or_else(|| RmkFsError::NotFound(name.to_string()))?;
+
+        debug!("Smart folder {}: {}", name, folder.query);
+
+        let batches = context.clone().sql(&folder.query).await?.collect().await?;
+
+        let mut ids = Vec::new();
+        for batch in batches {
+            let column = batch
+                .schema()
+                .index_of("id")
+                .map_err(|_| no_id_column(name))?;
+            let array = batch
+                .column(column)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| no_id_column(name))?;
+
+            ids.extend(array.iter().flatten().map(|id| id.to_string()));
+        }
+
+        self.results
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), ids.clone());
+
+        Ok(ids)
+    }
+
+    pub fn invalidate(&self) {
+        self.results.lock().unwrap().clear();
+    }
+}
+
+fn no_id_column(name: &str) -> RmkFsError {
+    RmkFsError::InvalidQuery(format!("{}: no text id column", name))
+}
+
+pub fn folder_id(name: &str) -> String {
+    format!("{}/{}", SMART_ID, name)
+}
+
+pub fn folder_name(id: &str) -> Option<&str> {
+    id.strip_prefix(SMART_ID)?.strip_prefix('/')
+}
+
+/// Whether `id` is the smart folders directory or one of them.
+pub fn is_smart(id: &str) -> bool {
+    id == SMART_ID || folder_name(id).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use datafusion::prelude::ExecutionContext;
+
+    use super::{SmartFolder, SmartFolders};
+    use crate::{
+        errors::{RmkFsError, RmkFsResult},
+        harness::SAMPLE_ID,
+        table::RmkTable,
+    };
+
+    fn folders(queries: &[(&str, &str)]) -> SmartFolders {
+        SmartFolders {
+            folders: queries
+                .iter()
+                .map(|(name, query)| SmartFolder {
+                    name: name.to_string(),
+                    query: query.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn from_file() -> RmkFsResult<()> {
+        let path = std::env::temp_dir().join(format!("rmk-smart-{}.toml", std::process::id()));
+
+        std::fs::write(
+            &path,
+            r#"
+            [[folder]]
+            name = "Pinned"
+            query = "SELECT id FROM metadata WHERE pinned"
+
+            [[folder]]
+            name = "Notebooks"
+            query = "SELECT id FROM metadata WHERE file_type = 'notebook'"
+            "#,
+        )?;
+        let smart = SmartFolders::from_file(&path)?;
+        assert_eq!(
+            smart.names().collect::<Vec<_>>(),
+            vec!["Pinned", "Notebooks"]
+        );
+        assert!(smart.contains("Notebooks"));
+
+        std::fs::write(&path, "[[folder]]\nname = \"No query\"\n")?;
+        assert!(matches!(
+            SmartFolders::from_file(&path),
+            Err(RmkFsError::ConfigError { .. })
+        ));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn documents() -> RmkFsResult<()> {
+        let table = RmkTable::new(&PathBuf::from("../rmk-notebook/samples"));
+        table.scan()?;
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("metadata", Arc::new(table))?;
+
+        let smart = folders(&[
+            (
+                "Notebooks",
+                "SELECT id FROM metadata WHERE file_type = 'notebook'",
+            ),
+            ("Pinned", "SELECT id FROM metadata WHERE pinned"),
+        ]);
+        smart.validate(&ctx)?;
+        assert_eq!(smart.documents(&ctx, "Notebooks").await?, vec![SAMPLE_ID]);
+        assert!(smart.documents(&ctx, "Pinned").await?.is_empty());
+        assert!(matches!(
+            smart.documents(&ctx, "Missing").await,
+            Err(RmkFsError::NotFound(_))
+        ));
+
+        for query in [
+            "SELECT name FROM metadata",
+            "SELECT version AS id FROM metadata",
+        ] {
+            let smart = folders(&[("Names", query)]);
+            assert!(matches!(
+                smart.validate(&ctx),
+                Err(RmkFsError::InvalidQuery(_))
+            ));
+            assert!(matches!(
+                smart.documents(&ctx, "Names").await,
+                Err(RmkFsError::InvalidQuery(_))
+            ));
+        }
+
+        assert!(matches!(
+            folders(&[("Broken", "SELECT FROM")]).validate(&ctx),
+            Err(RmkFsError::InvalidQuery(_))
+        ));
+
+        let query = "SELECT id FROM metadata";
+        for names in [
+            &[""][..],
+            &["Q1/Q2"],
+            &[".."],
+            &["Trailing dot."],
+            &["Pinned", "Pinned"],
+        ] {
+            let smart = folders(&names.iter().map(|name| (*name, query)).collect::<Vec<_>>());
+            assert!(matches!(
+                smart.validate(&ctx),
+                Err(RmkFsError::InvalidName(_))
+            ));
+        }
+
+        Ok(())
+    }
+}
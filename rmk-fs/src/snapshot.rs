@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use rmk_notebook::Metadata;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    errors::{RmkFsError, RmkFsResult},
+    hierarchy::{Entry, Hierarchy},
+    render::RenderCache,
+    table::ROOT_ID,
+};
+
+/// Name of the directory listing the snapshots in the mount root.
+pub const SNAPSHOTS_NAME: &str = ".snapshots";
+
+/// Synthetic id of that directory, snapshots are `<SNAPSHOTS_ID>/<name>` and
+/// their documents `<SNAPSHOTS_ID>/<name>/<id>`.
+pub const SNAPSHOTS_ID: &str = ".snapshots";
+
+/// Files of a document kept in snapshots, next to its `<id>/` folder of pages.
+/// Thumbnails and caches are left out, xochitl regenerates them.
+const DOCUMENT_EXTENSIONS: &[&str] = &["metadata", "content", "pagedata", "pdf", "epub"];
+
+/// Snapshots are named after the UTC time they were taken at, which also
+/// sorts them.
+const NAME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+/// Files of a document, by path relative to the xochitl root, and the hash of
+/// their content.
+type Tree = BTreeMap<String, String>;
+
+/// Past states of a xochitl root, kept in a directory of their own:
+///
+/// - `objects/` holds every file seen, named after the SHA-256 of its content,
+///   so unchanged files are stored once however many snapshots refer to them,
+/// - `snapshots/<name>.json` maps each document to the hash of its tree, the
+///   list of its files,
+/// - `checkouts/<name>/` is where documents of a snapshot are put back
+///   together to be rendered.
+///
+/// A snapshot is only recorded when a document changed since the last one.
+#[derive(Debug, Default)]
+pub struct Snapshots {
+    dir: Option<PathBuf>,
+    /// Hash of each file by path, valid while its stamp is the same
+    hashes: Mutex<HashMap<PathBuf, (Stamp, String)>>,
+    views: Mutex<HashMap<String, Arc<SnapshotView>>>,
+    /// Held while a snapshot is taken, so that two rescans do not record
+    /// the same change twice
+    taking: Mutex<()>,
+}
+
+/// What tells versions of a file apart without reading it. Edits that keep
+/// the size and mtime still change the ctime, and replacing the file the
+/// inode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    modified: SystemTime,
+    changed: (i64, i64),
+    inode: (u64, u64),
+}
+
+impl Stamp {
+    fn of(metadata: &std::fs::Metadata) -> RmkFsResult<Self> {
+        Ok(Stamp {
+            len: metadata.len(),
+            modified: metadata.modified()?,
+            changed: (metadata.ctime(), metadata.ctime_nsec()),
+            inode: (metadata.dev(), metadata.ino()),
+        })
+    }
+}
+
+/// The library as it was when a snapshot was taken.
+#[derive(Debug)]
+pub struct SnapshotView {
+    trees: HashMap<String, Tree>,
+    data: HashMap<String, Metadata>,
+    hierarchy: Hierarchy,
+    renders: RenderCache,
+}
+
+impl Snapshots {
+    pub fn open(dir: &Path) -> RmkFsResult<Self> {
+        for sub in ["objects", "snapshots", "checkouts"] {
+            std::fs::create_dir_all(dir.join(sub))?;
+        }
+
+        info!("Keeping snapshots in {}", dir.display());
+
+        Ok(Snapshots {
+            dir: Some(dir.to_path_buf()),
+            ..Default::default()
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Records the current state of `root`, returning the name of the new
+    /// snapshot or `None` if nothing changed since the last one.
+    pub fn take(&self, root: &Path) -> RmkFsResult<Option<String>> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let _taking = self.taking.lock().unwrap();
+
+        let mut manifest = BTreeMap::new();
+        for (id, files) in document_files(root)? {
+            let mut tree = Tree::new();
+            for file in files {
+                let path = file.strip_prefix(root).unwrap_or(&file);
+                tree.insert(path.to_string_lossy().into_owned(), self.store(&file)?);
+            }
+
+            manifest.insert(id, self.store_bytes(&serde_json::to_vec(&tree)?)?);
+        }
+
+        let previous = match self.names()?.last() {
+            Some(name) => self.manifest(name)?,
+            None => BTreeMap::new(),
+        };
+
+        if previous == manifest {
+            debug!("No change since the last snapshot");
+            return Ok(None);
+        }
+
+        let changed = manifest
+            .iter()
+            .filter(|(id, tree)| previous.get(*id) != Some(*tree))
+            .count();
+
+        let name = self.next_name(dir);
+        std::fs::write(
+            dir.join("snapshots").join(format!("{}.json", name)),
+            serde_json::to_vec_pretty(&manifest)?,
+        )?;
+
+        info!(
+            "Snapshot {}: {} documents, {} changed",
+            name,
+            manifest.len(),
+            changed
+        );
+
+        Ok(Some(name))
+    }
+
+    /// Names of the snapshots, oldest first.
+    pub fn names(&self) -> RmkFsResult<Vec<String>> {
+        let dir = match &self.dir {
+            Some(dir) => dir.join("snapshots"),
+            None => return Ok(vec![]),
+        };
+
+        let mut names: Vec<String> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry
+                    .file_name()
+                    .to_str()?
+                    .strip_suffix(".json")?
+                    .to_string();
+                Some(name)
+            })
+            .collect();
+        names.sort();
+
+        Ok(names)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names()
+            .is_ok_and(|names| names.iter().any(|n| n == name))
+    }
+
+    /// Snapshot `name`, read once and kept since snapshots never change.
+    pub fn view(&self, name: &str) -> RmkFsResult<Arc<SnapshotView>> {
+        if let Some(view) = self.views.lock().unwrap().get(name) {
+            return Ok(view.clone());
+        }
+
+        let mut trees = HashMap::new();
+        let mut data = HashMap::new();
+
+        for (id, tree) in self.manifest(name)? {
+            let tree: Tree = serde_json::from_slice(&self.load(&tree)?)?;
+
+            if let Some(hash) = tree.get(&format!("{}.metadata", id)) {
+                let metadata: Metadata = serde_json::from_slice(&self.load(hash)?)?;
+                data.insert(id.clone(), metadata);
+            }
+
+            trees.insert(id, tree);
+        }
+
+        let view = Arc::new(SnapshotView {
+            trees,
+            hierarchy: Hierarchy::build(&data),
+            data,
+            renders: RenderCache::default(),
+        });
+
+        self.views
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), view.clone());
+
+        Ok(view)
+    }
+
+    /// Puts the files of document `id` in snapshot `name` back in place and
+    /// returns the xochitl root they are in.
+    pub fn checkout(&self, name: &str, id: &str) -> RmkFsResult<PathBuf> {
+        let view = self.view(name)?;
+        let tree = view
+            .trees
+            .get(id)
+            .ok_or_else(|| RmkFsError::NotFound(format!("{} in snapshot {}", id, name)))?;
+
+        let root = self.dir()?.join("checkouts").join(name);
+        for (path, hash) in tree {
+            let target = root.join(path);
+            if target.exists() {
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            // Objects are read-only, sharing them is safe
+            let object = self.object(hash)?;
+            if std::fs::hard_link(&object, &target).is_err() {
+                std::fs::copy(&object, &target)?;
+            }
+        }
+
+        Ok(root)
+    }
+
+    /// Checks out every document of snapshot `name`, see
+    /// [`Snapshots::checkout`].
+    pub fn checkout_all(&self, name: &str) -> RmkFsResult<PathBuf> {
+        for id in self.view(name)?.ids() {
+            self.checkout(name, id)?;
+        }
+
+        Ok(self.dir()?.join("checkouts").join(name))
+    }
+
+    /// PDF of document `id` as it was in snapshot `name`.
+    pub fn render(&self, name: &str, id: &str) -> RmkFsResult<Arc<Vec<u8>>> {
+        let root = self.checkout(name, id)?;
+        self.view(name)?.renders.get_or_render(&root, id)
+    }
+
+    /// Size of the PDF of `id` in snapshot `name`, once known.
+    pub fn size(&self, name: &str, id: &str) -> u64 {
+        let view = match self.view(name) {
+            Ok(view) => view,
+            Err(_) => return 0,
+        };
+
+        if let Some(pdf) = view.renders.get(id) {
+            return pdf.len() as u64;
+        }
+
+        view.trees
+            .get(id)
+            .and_then(|tree| tree.get(&format!("{}.pdf", id)))
+            .and_then(|hash| self.object(hash).ok())
+            .and_then(|object| std::fs::metadata(object).ok())
+            .map_or(0, |metadata| metadata.len())
+    }
+
+    fn dir(&self) -> RmkFsResult<&PathBuf> {
+        self.dir
+            .as_ref()
+            .ok_or_else(|| RmkFsError::NotFound(SNAPSHOTS_NAME.to_string()))
+    }
+
+    fn manifest(&self, name: &str) -> RmkFsResult<BTreeMap<String, String>> {
+        let path = self.dir()?.join("snapshots").join(format!("{}.json", name));
+
+        match std::fs::read(&path) {
+            Ok(manifest) => Ok(serde_json::from_slice(&manifest)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(RmkFsError::NotFound(format!("snapshot {}", name)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Stores the content of `file`, hashing it only if it changed since it
+    /// was last stored.
+    fn store(&self, file: &Path) -> RmkFsResult<String> {
+        let stamp = Stamp::of(&std::fs::metadata(file)?)?;
+
+        if let Some((known, hash)) = self.hashes.lock().unwrap().get(file) {
+            if *known == stamp {
+                return Ok(hash.clone());
+            }
+        }
+
+        let hash = self.store_bytes(&std::fs::read(file)?)?;
+        self.hashes
+            .lock()
+            .unwrap()
+            .insert(file.to_path_buf(), (stamp, hash.clone()));
+
+        Ok(hash)
+    }
+
+    fn store_bytes(&self, bytes: &[u8]) -> RmkFsResult<String> {
+        let hash: String = Sha256::digest(bytes)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        let object = self.object(&hash)?;
+        if !object.exists() {
+            std::fs::create_dir_all(object.parent().unwrap())?;
+
+            // Written aside first, an interrupted write must not leave a
+            // truncated object behind a valid hash
+            let partial = object.with_extension("partial");
+            let _ = std::fs::remove_file(&partial);
+            std::fs::write(&partial, bytes)?;
+
+            let mut permissions = std::fs::metadata(&partial)?.permissions();
+            permissions.set_readonly(true);
+            std::fs::set_permissions(&partial, permissions)?;
+
+            std::fs::rename(&partial, &object)?;
+        }
+
+        Ok(hash)
+    }
+
+    fn load(&self, hash: &str) -> RmkFsResult<Vec<u8>> {
+        Ok(std::fs::read(self.object(hash)?)?)
+    }
+
+    fn object(&self, hash: &str) -> RmkFsResult<PathBuf> {
+        let (prefix, rest) = hash.split_at(2.min(hash.len()));
+        Ok(self.dir()?.join("objects").join(prefix).join(rest))
+    }
+
+    /// A name for a snapshot taken now, unique even for several snapshots in
+    /// the same second.
+    fn next_name(&self, dir: &Path) -> String {
+        let now: DateTime<Utc> = SystemTime::now().into();
+        let name = now.format(NAME_FORMAT).to_string();
+
+        (1..)
+            .map(|n| match n {
+                1 => name.clone(),
+                n => format!("{}.{}", name, n),
+            })
+            .find(|name| {
+                !dir.join("snapshots")
+                    .join(format!("{}.json", name))
+                    .exists()
+            })
+            .unwrap()
+    }
+}
+
+impl SnapshotView {
+    /// Documents and folders of folder `id` in the snapshot, trash excluded.
+    pub fn children(&self, id: &str) -> &[Entry] {
+        self.hierarchy.children(id)
+    }
+
+    pub fn parent(&self, id: &str) -> Option<&str> {
+        self.hierarchy.parent(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Metadata> {
+        self.data.get(id)
+    }
+
+    /// Ids of every document and folder in the snapshot.
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.trees.keys()
+    }
+}
+
+/// Files of each document of `root`, grouped by id.
+fn document_files(root: &Path) -> RmkFsResult<BTreeMap<String, Vec<PathBuf>>> {
+    let mut documents: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+
+        let is_metadata = path.extension().is_some_and(|ext| ext == "metadata");
+        if let (true, Some(id)) = (is_metadata, path.file_stem().and_then(|s| s.to_str())) {
+            documents.insert(id.to_string(), Vec::new());
+        }
+    }
+
+    for (id, files) in documents.iter_mut() {
+        for extension in DOCUMENT_EXTENSIONS {
+            let file = root.join(format!("{}.{}", id, extension));
+            if file.is_file() {
+                files.push(file);
+            }
+        }
+
+        let pages = root.join(id.as_str());
+        if pages.is_dir() {
+            for entry in std::fs::read_dir(pages)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        }
+
+        files.sort();
+    }
+
+    Ok(documents)
+}
+
+pub fn snapshot_id(name: &str, id: &str) -> String {
+    if id == ROOT_ID {
+        format!("{}/{}", SNAPSHOTS_ID, name)
+    } else {
+        format!("{}/{}/{}", SNAPSHOTS_ID, name, id)
+    }
+}
+
+/// Snapshot name and document id of a node under [`SNAPSHOTS_ID`], the
+/// document being [`ROOT_ID`] for the snapshot directory itself.
+pub fn split_id(id: &str) -> Option<(&str, &str)> {
+    let rest = id.strip_prefix(SNAPSHOTS_ID)?.strip_prefix('/')?;
+
+    Some(rest.split_once('/').unwrap_or((rest, ROOT_ID)))
+}
+
+/// Whether `id` is the snapshots directory or anything below it.
+pub fn is_snapshot(id: &str) -> bool {
+    id == SNAPSHOTS_ID || split_id(id).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{snapshot_id, split_id, Snapshots};
+    use crate::{errors::RmkFsResult, harness::SAMPLE_ID, table::ROOT_ID};
+
+    #[test]
+    fn ids() {
+        assert_eq!(split_id(&snapshot_id("s", ROOT_ID)), Some(("s", ROOT_ID)));
+        assert_eq!(split_id(&snapshot_id("s", "a")), Some(("s", "a")));
+        assert_eq!(split_id(".snapshots"), None);
+        assert_eq!(split_id(".smart/s"), None);
+    }
+
+    #[test]
+    fn take_view_and_render() -> RmkFsResult<()> {
+        let tmp = std::env::temp_dir().join(format!("rmk-snapshots-{}", std::process::id()));
+        let root = tmp.join("xochitl");
+        crate::harness::copy_dir(Path::new("../rmk-notebook/samples"), &root)?;
+
+        let snapshots = Snapshots::open(&tmp.join("store"))?;
+        let first = snapshots.take(&root)?.expect("first snapshot");
+        assert_eq!(snapshots.take(&root)?, None);
+
+        let metadata = root.join(format!("{}.metadata", SAMPLE_ID));
+        let renamed = std::fs::read_to_string(&metadata)?.replace("Hedged", "Renamed");
+        std::fs::write(&metadata, renamed)?;
+
+        let second = snapshots.take(&root)?.expect("second snapshot");
+        assert_eq!(snapshots.names()?, vec![first.clone(), second.clone()]);
+
+        let name = |snapshot: &str| -> RmkFsResult<String> {
+            let view = snapshots.view(snapshot)?;
+            Ok(view.get(SAMPLE_ID).unwrap().visible_name.clone())
+        };
+        assert_eq!(name(&first)?, "Hedged shared class");
+        assert_eq!(name(&second)?, "Renamed shared class");
+
+        let pdf = snapshots.render(&first, SAMPLE_ID)?;
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(snapshots.size(&first, SAMPLE_ID), pdf.len() as u64);
+
+        std::fs::remove_dir_all(tmp)?;
+        Ok(())
+    }
+
+    #[test]
+    fn same_size_and_mtime_edit_is_stored() -> RmkFsResult<()> {
+        let tmp = std::env::temp_dir().join(format!("rmk-stamps-{}", std::process::id()));
+        std::fs::create_dir_all(&tmp)?;
+        let snapshots = Snapshots::open(&tmp.join("store"))?;
+
+        let file = tmp.join("page.rm");
+        std::fs::write(&file, "first")?;
+        let mtime = std::fs::metadata(&file)?.modified()?;
+        let first = snapshots.store(&file)?;
+        assert_eq!(snapshots.store(&file)?, first);
+
+        std::fs::write(&file, "other")?;
+        std::fs::File::options()
+            .write(true)
+            .open(&file)?
+            .set_modified(mtime)?;
+        assert_ne!(snapshots.store(&file)?, first);
+
+        std::fs::remove_dir_all(tmp)?;
+        Ok(())
+    }
+}
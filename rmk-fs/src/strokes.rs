@@ -0,0 +1,439 @@
+use std::{any::Any, sync::Arc};
+
+use arrow::{
+    array::{
+        ArrayBuilder, Float32Builder, ListBuilder, StringBuilder, StructBuilder, UInt64Builder,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use datafusion::{
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider},
+    error::DataFusionError,
+    execution::runtime_env::RuntimeEnv,
+    logical_plan::Expr,
+    physical_plan::{
+        expressions::PhysicalSortExpr, memory::MemoryStream, project_schema, ExecutionPlan,
+        Partitioning, SendableRecordBatchStream, Statistics,
+    },
+};
+use log::warn;
+use rmk_notebook::{read_page_with_id, Page};
+
+use crate::{errors::RmkFsResult, filter::column_equals, table::RmkTable};
+
+const POINT_FIELDS: [&str; 6] = ["x", "y", "speed", "direction", "width", "pressure"];
+
+fn point_fields() -> Vec<Field> {
+    POINT_FIELDS
+        .iter()
+        .map(|name| Field::new(name, DataType::Float32, false))
+        .collect()
+}
+
+/// One row per line drawn on any page of the documents in a [`RmkTable`],
+/// with its points as a list of structs.
+///
+/// Strokes are only parsed when a query reads them, one document per
+/// partition. A `document_id = '...'` filter restricts which documents are
+/// read at all.
+#[derive(Clone, Debug)]
+pub struct StrokesTable {
+    table: RmkTable,
+    schema: SchemaRef,
+}
+
+impl StrokesTable {
+    pub fn new(table: RmkTable) -> Self {
+        Self {
+            table,
+            schema: SchemaRef::new(Schema::new(vec![
+                Field::new("document_id", DataType::Utf8, false),
+                Field::new("page_id", DataType::Utf8, false),
+                Field::new("page_index", DataType::UInt64, false),
+                Field::new("layer", DataType::UInt64, false),
+                Field::new("line_index", DataType::UInt64, false),
+                Field::new("brush", DataType::Utf8, false),
+                Field::new("color", DataType::Utf8, false),
+                Field::new("base_size", DataType::Float32, false),
+                Field::new(
+                    "points",
+                    DataType::List(Box::new(Field::new(
+                        "item",
+                        DataType::Struct(point_fields()),
+                        true,
+                    ))),
+                    false,
+                ),
+            ])),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for StrokesTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        // DataFusion still applies the filter, it only narrows what is parsed
+        match column_equals(filter, "document_id") {
+            Some(_) => Ok(TableProviderFilterPushDown::Inexact),
+            None => Ok(TableProviderFilterPushDown::Unsupported),
+        }
+    }
+
+    async fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let wanted: Vec<String> = filters
+            .iter()
+            .filter_map(|filter| column_equals(filter, "document_id"))
+            .collect();
+
+        let mut documents: Vec<String> = self
+            .table
+            .documents()
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| wanted.is_empty() || wanted.contains(id))
+            .collect();
+        documents.sort();
+
+        Ok(Arc::new(StrokesExecPlan {
+            table: self.table.clone(),
+            schema: self.schema.clone(),
+            projected_schema: project_schema(&self.schema, projection.as_ref())?,
+            projection: projection.clone(),
+            documents,
+        }))
+    }
+}
+
+#[derive(Clone, Debug)]
+struct StrokesExecPlan {
+    table: RmkTable,
+    schema: SchemaRef,
+    projected_schema: SchemaRef,
+    projection: Option<Vec<usize>>,
+    documents: Vec<String>,
+}
+
+impl StrokesExecPlan {
+    /// Pages of `id` in order, `None` for blank ones.
+    fn pages(&self, id: &str) -> RmkFsResult<Vec<(String, Option<Page>)>> {
+        let root = self.table.root();
+
+        self.table
+            .content(id)?
+            .pages
+            .into_iter()
+            .map(|page_id| {
+                let page = read_page_with_id(&root, id, &page_id)?;
+                Ok((page_id, page))
+            })
+            .collect()
+    }
+
+    fn record_batch(&self, id: &str) -> Result<RecordBatch, DataFusionError> {
+        let pages = self.pages(id).unwrap_or_else(|e| {
+            warn!("Skipping strokes of {}: {}", id, e);
+            vec![]
+        });
+
+        let mut document_id_array = StringBuilder::new(0);
+        let mut page_id_array = StringBuilder::new(0);
+        let mut page_index_array = UInt64Builder::new(0);
+        let mut layer_array = UInt64Builder::new(0);
+        let mut line_index_array = UInt64Builder::new(0);
+        let mut brush_array = StringBuilder::new(0);
+        let mut color_array = StringBuilder::new(0);
+        let mut base_size_array = Float32Builder::new(0);
+        let mut points_array = ListBuilder::new(StructBuilder::new(
+            point_fields(),
+            POINT_FIELDS
+                .iter()
+                .map(|_| Box::new(Float32Builder::new(0)) as Box<dyn ArrayBuilder>)
+                .collect(),
+        ));
+
+        for (page_index, (page_id, page)) in pages.iter().enumerate() {
+            for (layer_index, layer) in page.iter().flat_map(|p| &p.layers).enumerate() {
+                for (line_index, line) in layer.lines.iter().enumerate() {
+                    document_id_array.append_value(id)?;
+                    page_id_array.append_value(page_id)?;
+                    page_index_array.append_value(page_index as u64)?;
+                    layer_array.append_value(layer_index as u64)?;
+                    line_index_array.append_value(line_index as u64)?;
+                    brush_array.append_value(format!("{:?}", line.brush_type))?;
+                    color_array.append_value(format!("{:?}", line.color))?;
+                    base_size_array.append_value(line.brush_base_size)?;
+
+                    let points = points_array.values();
+                    for pt in &line.points {
+                        let values = [pt.x, pt.y, pt.speed, pt.direction, pt.width, pt.pressure];
+                        for (i, value) in values.iter().enumerate() {
+                            points
+                                .field_builder::<Float32Builder>(i)
+                                .expect("point fields are Float32")
+                                .append_value(*value)?;
+                        }
+                        points.append(true)?;
+                    }
+                    points_array.append(true)?;
+                }
+            }
+        }
+
+        Ok(RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(document_id_array.finish()),
+                Arc::new(page_id_array.finish()),
+                Arc::new(page_index_array.finish()),
+                Arc::new(layer_array.finish()),
+                Arc::new(line_index_array.finish()),
+                Arc::new(brush_array.finish()),
+                Arc::new(color_array.finish()),
+                Arc::new(base_size_array.finish()),
+                Arc::new(points_array.finish()),
+            ],
+        )?)
+    }
+}
+
+#[async_trait]
+impl ExecutionPlan for StrokesExecPlan {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.projected_schema.clone()
+    }
+
+    /// One partition per document
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(self.documents.len().max(1))
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn relies_on_input_order(&self) -> bool {
+        false
+    }
+
+    fn with_new_children(
+        &self,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+        if children.is_empty() {
+            Ok(Arc::new(self.clone()))
+        } else {
+            Err(DataFusionError::Internal(
+                "StrokesExecPlan has no children".to_string(),
+            ))
+        }
+    }
+
+    async fn execute(
+        &self,
+        partition: usize,
+        _runtime: Arc<RuntimeEnv>,
+    ) -> Result<SendableRecordBatchStream, DataFusionError> {
+        let batches = match self.documents.get(partition) {
+            Some(id) => {
+                // Parsing strokes is CPU bound, keep it off the async workers
+                let (plan, id) = (self.clone(), id.clone());
+                let batch = tokio::task::spawn_blocking(move || plan.record_batch(&id))
+                    .await
+                    .map_err(|e| DataFusionError::Execution(e.to_string()))??;
+                vec![batch]
+            }
+            None => vec![],
+        };
+
+        Ok(Box::pin(MemoryStream::try_new(
+            batches,
+            self.projected_schema.clone(),
+            self.projection.clone(),
+        )?))
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use arrow::array::{Array, Float32Array, ListArray, StringArray, StructArray};
+    use datafusion::{
+        datasource::TableProvider,
+        logical_plan::{col, lit},
+        physical_plan::collect,
+        prelude::ExecutionContext,
+    };
+    use rmk_notebook::{
+        generate::{generate, GeneratorConfig},
+        read_page_with_id,
+    };
+
+    use super::{StrokesTable, POINT_FIELDS};
+    use crate::{errors::RmkFsResult, table::RmkTable};
+
+    const SAMPLE_ID: &str = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+    #[tokio::test]
+    async fn projected_query() -> RmkFsResult<()> {
+        let table = RmkTable::new(&PathBuf::from("../rmk-notebook/samples"));
+        table.scan()?;
+
+        let strokes = StrokesTable::new(table);
+
+        // Streams report the projected schema, as their plan does
+        let plan = TableProvider::scan(&strokes, &Some(vec![6, 4]), &[], None).await?;
+        let mut ctx = ExecutionContext::new();
+        let stream = plan.execute(0, ctx.runtime_env()).await?;
+        assert_eq!(stream.schema(), plan.schema());
+        assert_eq!(stream.schema().fields().len(), 2);
+
+        ctx.register_table("strokes", Arc::new(strokes))?;
+
+        let query = format!(
+            "SELECT color, line_index FROM strokes WHERE document_id = '{}'",
+            SAMPLE_ID
+        );
+        let batches = ctx.sql(&query).await?.collect().await?;
+
+        let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        assert!(rows > 0);
+
+        let batch = batches.iter().find(|batch| batch.num_rows() > 0).unwrap();
+        assert_eq!(batch.num_columns(), 2);
+        assert_eq!(batch.schema().field(0).name(), "color");
+
+        let colors = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert!((0..colors.len()).all(|i| !colors.value(i).is_empty()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn document_id_pushdown() -> RmkFsResult<()> {
+        let root = std::env::temp_dir().join(format!("rmk-strokes-{}", std::process::id()));
+        let library = generate(&root, &GeneratorConfig::default())?;
+
+        let table = RmkTable::new(&root);
+        table.scan()?;
+        let documents = table.documents().len();
+        assert!(documents > 1);
+
+        let strokes = StrokesTable::new(table);
+
+        let plan = TableProvider::scan(&strokes, &None, &[], None).await?;
+        assert_eq!(plan.output_partitioning().partition_count(), documents);
+
+        // Only the filtered document is read, in a single partition
+        let filter = col("document_id").eq(lit(library.documents[0].as_str()));
+        let plan = TableProvider::scan(&strokes, &None, &[filter], None).await?;
+        assert_eq!(plan.output_partitioning().partition_count(), 1);
+
+        let filter = col("document_id").eq(lit("missing"));
+        let plan = TableProvider::scan(&strokes, &None, &[filter], None).await?;
+        let ctx = ExecutionContext::new();
+        let batches = collect(plan, ctx.runtime_env()).await?;
+        assert_eq!(
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+            0
+        );
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn points() -> RmkFsResult<()> {
+        let root = PathBuf::from("../rmk-notebook/samples");
+        let table = RmkTable::new(&root);
+        table.scan()?;
+
+        // First line of the first page with any
+        let (page_index, expected) = table
+            .content(SAMPLE_ID)?
+            .pages
+            .iter()
+            .enumerate()
+            .find_map(|(index, page_id)| {
+                let page = read_page_with_id(&root, SAMPLE_ID, page_id).unwrap()?;
+                let points = page.layers.first()?.lines.first()?.points.clone();
+                Some((index, points))
+            })
+            .unwrap();
+
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("strokes", Arc::new(StrokesTable::new(table)))?;
+
+        let query = format!(
+            "SELECT points FROM strokes WHERE document_id = '{}' AND page_index = {} \
+             AND layer = 0 AND line_index = 0",
+            SAMPLE_ID, page_index
+        );
+        let batches = ctx.sql(&query).await?.collect().await?;
+        let batch = batches.iter().find(|batch| batch.num_rows() > 0).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+
+        let list = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap()
+            .value(0);
+        let points = list.as_any().downcast_ref::<StructArray>().unwrap();
+        assert_eq!(points.len(), expected.len());
+
+        let value = |name: &str, i: usize| {
+            let column = POINT_FIELDS
+                .iter()
+                .position(|field| *field == name)
+                .unwrap();
+            points
+                .column(column)
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .unwrap()
+                .value(i)
+        };
+        for (i, pt) in expected.iter().enumerate() {
+            assert_eq!(value("x", i), pt.x);
+            assert_eq!(value("y", i), pt.y);
+            assert_eq!(value("pressure", i), pt.pressure);
+        }
+
+        Ok(())
+    }
+}
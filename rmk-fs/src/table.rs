@@ -1,32 +1,108 @@
 use arrow::{
-    array::StringBuilder,
-    datatypes::{DataType, Field, Schema, SchemaRef},
+    array::{
+        ArrayRef, BooleanArray, ListBuilder, StringArray, StringBuilder, TimestampMillisecondArray,
+        UInt64Array,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    error::ArrowError,
     record_batch::RecordBatch,
 };
 use async_trait::async_trait;
 use datafusion::{
-    datasource::TableProvider,
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider},
     error::DataFusionError,
+    logical_plan::Expr,
     physical_plan::{
-        memory::MemoryStream, project_schema, ExecutionPlan, SendableRecordBatchStream,
+        memory::MemoryStream, project_schema, ExecutionPlan, SendableRecordBatchStream, Statistics,
     },
+    prelude::ExecutionContext,
 };
 use glob::glob;
 use std::{
     any::Any,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, info, warn};
+use rmk_notebook::{
+    read_content_with_id, read_metadata, write_metadata_with_id, Content, Metadata,
 };
 
-use log::{debug, info};
-use rmk_notebook::{read_metadata, Metadata};
+use crate::{
+    errors::{RmkFsError, RmkFsResult},
+    filter::column_equals,
+    hierarchy::{is_reserved, visible_name, Entry, Hierarchy, Issue},
+    names::decode,
+    pages::PagesTable,
+    strokes::StrokesTable,
+};
+
+/// Parent id xochitl uses for documents at the top level.
+pub const ROOT_ID: &str = "";
+
+/// Parent id xochitl uses for documents moved to the trash.
+pub const TRASH_ID: &str = "trash";
+
+/// Documents added, changed and removed by a [`RmkTable::scan`], and the
+/// metadata files that could not be read.
+#[derive(Clone, Debug, Default)]
+pub struct ScanReport {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl ScanReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+            && self.failed.is_empty()
+    }
+}
+
+impl Display for ScanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} changed, {} removed, {} failed",
+            self.added.len(),
+            self.changed.len(),
+            self.removed.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// Columns whose equality filters are answered by the table itself.
+const PUSHDOWN_COLUMNS: [&str; 3] = ["id", "parent", "type"];
+
+fn pushed_down(filter: &Expr) -> Option<(&'static str, String)> {
+    PUSHDOWN_COLUMNS
+        .iter()
+        .find_map(|column| column_equals(filter, column).map(|value| (*column, value)))
+}
 
-use crate::errors::{RmkFsError, RmkFsResult};
+/// Timestamps, version, page count, size and flags of a row.
+const FIXED_ROW_BYTES: usize = 5 * 8 + 3;
+
+/// One row of the `metadata` table, before projection.
+type Row<'a> = (&'a String, &'a Metadata);
 
 struct RmkTableInner {
     data: HashMap<String, Metadata>,
+    /// Read along with the metadata, missing for documents without a
+    /// readable `.content` file
+    contents: HashMap<String, Content>,
+    /// Modification time of each `.metadata` file when it was last read
+    mtimes: HashMap<String, SystemTime>,
+    /// Rebuilt whenever `data` changes
+    hierarchy: Hierarchy,
     root: PathBuf,
 }
 
@@ -34,11 +110,14 @@ impl RmkTableInner {
     fn new(root: PathBuf) -> RmkTableInner {
         RmkTableInner {
             data: HashMap::new(),
+            contents: HashMap::new(),
+            mtimes: HashMap::new(),
+            hierarchy: Hierarchy::default(),
             root,
         }
     }
 
-    fn scan(&mut self) -> RmkFsResult<()> {
+    fn scan(&mut self) -> RmkFsResult<ScanReport> {
         info!("Scanning filesystem at {}", self.root.display());
         let pattern = self.root.join("*.metadata");
 
@@ -46,20 +125,331 @@ impl RmkTableInner {
             root: self.root.clone(),
         })?;
 
-        let files = glob(pattern)
-            .map_err(|_source| RmkFsError::ScanError {
-                root: self.root.clone(),
-            })?
-            .flatten();
+        let files = glob(pattern).map_err(|_source| RmkFsError::ScanError {
+            root: self.root.clone(),
+        })?;
+
+        let mut report = ScanReport::default();
+        let mut seen = HashSet::new();
 
         for f in files {
+            let f = match f {
+                Ok(f) => f,
+                Err(e) => {
+                    report
+                        .failed
+                        .push((e.path().to_path_buf(), e.error().to_string()));
+                    continue;
+                }
+            };
+
             debug!("{:?}", f);
 
-            let (id, metadata) = read_metadata(&f)?;
-            self.data.insert(id.to_string(), metadata);
+            let id = match f.file_stem().and_then(|stem| stem.to_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+
+            // A broken file keeps whatever was read from it before
+            seen.insert(id.clone());
+
+            let mtime = match std::fs::metadata(&f).and_then(|m| m.modified()) {
+                Ok(mtime) => mtime,
+                Err(e) => {
+                    report.failed.push((f, e.to_string()));
+                    continue;
+                }
+            };
+
+            if self.mtimes.get(&id) == Some(&mtime) {
+                continue;
+            }
+
+            let metadata = match read_metadata(&f) {
+                Ok((_, metadata)) => metadata,
+                Err(e) => {
+                    report.failed.push((f, e.to_string()));
+                    continue;
+                }
+            };
+
+            self.mtimes.insert(id.clone(), mtime);
+            self.read_content(&id);
+
+            match self.data.insert(id.clone(), metadata.clone()) {
+                None => report.added.push(id),
+                Some(previous)
+                    if previous._version != metadata._version
+                        || previous.last_modified != metadata.last_modified =>
+                {
+                    report.changed.push(id)
+                }
+                Some(_) => {}
+            }
         }
 
-        Ok(())
+        let removed: Vec<String> = self
+            .data
+            .keys()
+            .filter(|id| !seen.contains(*id))
+            .cloned()
+            .collect();
+
+        for id in removed {
+            self.data.remove(&id);
+            self.contents.remove(&id);
+            self.mtimes.remove(&id);
+            report.removed.push(id);
+        }
+
+        self.hierarchy = Hierarchy::build(&self.data);
+
+        for (path, e) in &report.failed {
+            warn!("Skipping {}: {}", path.display(), e);
+        }
+        info!("Scanned {}: {}", self.root.display(), report);
+
+        Ok(report)
+    }
+
+    /// Re-reads the metadata of `id`, returning it before and after.
+    fn reload(&mut self, id: &str) -> RmkFsResult<(Option<Metadata>, Option<Metadata>)> {
+        let path = self.root.join(format!("{}.metadata", id));
+
+        let after = if path.exists() {
+            let mtime = std::fs::metadata(&path)?.modified()?;
+            let (_, metadata) = read_metadata(&path)?;
+
+            self.mtimes.insert(id.to_string(), mtime);
+            self.read_content(id);
+            Some(metadata)
+        } else {
+            self.mtimes.remove(id);
+            self.contents.remove(id);
+            None
+        };
+
+        let before = match &after {
+            Some(metadata) => self.data.insert(id.to_string(), metadata.clone()),
+            None => self.data.remove(id),
+        };
+
+        self.hierarchy = Hierarchy::build(&self.data);
+
+        Ok((before, after))
+    }
+
+    /// Records the modification time of a metadata file written by RmkFs, so
+    /// the next scan does not read it back.
+    fn stat(&mut self, id: &str) {
+        let path = self.root.join(format!("{}.metadata", id));
+
+        if let Ok(mtime) = std::fs::metadata(path).and_then(|m| m.modified()) {
+            self.mtimes.insert(id.to_string(), mtime);
+        }
+    }
+
+    fn read_content(&mut self, id: &str) {
+        match read_content_with_id(&self.root, id) {
+            Ok(content) => {
+                self.contents.insert(id.to_string(), content);
+            }
+            Err(e) => {
+                debug!("No content for {}: {}", id, e);
+                self.contents.remove(id);
+            }
+        }
+    }
+
+    /// Entries matching every `(column, value)` equality of `filters`, at
+    /// most `limit` of them.
+    fn matching<'a>(
+        &'a self,
+        filters: &'a [(&'static str, String)],
+        limit: Option<usize>,
+    ) -> impl Iterator<Item = (&'a String, &'a Metadata)> + 'a {
+        // An id filter is a single map lookup
+        let candidates: Box<dyn Iterator<Item = (&String, &Metadata)>> =
+            match filters.iter().find(|(column, _)| *column == "id") {
+                Some((_, id)) => Box::new(self.data.get_key_value(id).into_iter()),
+                None => Box::new(self.data.iter()),
+            };
+
+        candidates
+            .filter(move |(id, metadata)| {
+                filters.iter().all(|(column, value)| match *column {
+                    "id" => *id == value,
+                    "parent" => metadata.parent == *value,
+                    "type" => metadata.typ == *value,
+                    _ => true,
+                })
+            })
+            .take(limit.unwrap_or(usize::MAX))
+    }
+
+    /// Content columns of a row. Folders have no pages nor file type.
+    fn row_content(&self, (id, metadata): &Row) -> Option<&Content> {
+        self.contents
+            .get(id.as_str())
+            .filter(|_| !metadata.is_collection())
+    }
+
+    /// Column `name` of the `metadata` table for `rows`.
+    fn column(&self, name: &str, rows: &[Row]) -> Result<ArrayRef, ArrowError> {
+        let array: ArrayRef = match name {
+            "id" => Arc::new(StringArray::from_iter_values(rows.iter().map(|(id, _)| id))),
+            "type" => Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|(_, metadata)| &metadata.typ),
+            )),
+            "name" => Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|(_, metadata)| &metadata.visible_name),
+            )),
+            "parent" => Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|(_, metadata)| &metadata.parent),
+            )),
+            "path" => Arc::new(StringArray::from_iter_values(
+                rows.iter()
+                    .map(|(id, _)| self.hierarchy.path(id).unwrap_or_default()),
+            )),
+            "last_modified" => Arc::new(TimestampMillisecondArray::from_vec(
+                rows.iter()
+                    .map(|(_, metadata)| millis(metadata.last_modified))
+                    .collect(),
+                None,
+            )),
+            "last_opened" => Arc::new(TimestampMillisecondArray::from_opt_vec(
+                rows.iter()
+                    .map(|(_, metadata)| metadata.last_opened.map(millis))
+                    .collect(),
+                None,
+            )),
+            "version" => Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|(_, metadata)| metadata._version as u64),
+            )),
+            "pinned" => Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|(_, metadata)| Some(metadata._pinned)),
+            )),
+            "deleted" => Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|(_, metadata)| Some(metadata._deleted)),
+            )),
+            "synced" => Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|(_, metadata)| Some(metadata._synced)),
+            )),
+            "file_type" => Arc::new(StringArray::from_iter(rows.iter().map(|row| {
+                self.row_content(row)
+                    .and_then(|content| content.file_type.as_deref())
+            }))),
+            "page_count" => Arc::new(UInt64Array::from_iter(rows.iter().map(|row| {
+                self.row_content(row)
+                    .map(|content| content.page_count as u64)
+            }))),
+            "orientation" => Arc::new(StringArray::from_iter(rows.iter().map(|row| {
+                self.row_content(row)
+                    .map(|content| content.orientation.as_str())
+            }))),
+            "size_in_bytes" => Arc::new(UInt64Array::from_iter(rows.iter().map(|row| {
+                self.row_content(row)
+                    .and_then(|content| content.size_in_bytes.as_ref())
+                    .and_then(|size| size.parse::<u64>().ok())
+            }))),
+            "tags" => {
+                let mut tags = ListBuilder::new(StringBuilder::new(rows.len()));
+                for row in rows {
+                    for tag in self.row_content(row).iter().flat_map(|c| &c.tags) {
+                        tags.values().append_value(&tag.name)?;
+                    }
+                    tags.append(true)?;
+                }
+                Arc::new(tags.finish())
+            }
+            _ => {
+                return Err(ArrowError::SchemaError(format!(
+                    "no column {} in metadata",
+                    name
+                )))
+            }
+        };
+
+        Ok(array)
+    }
+
+    /// Applies `f` to the metadata of `id` and persists it the way xochitl
+    /// does for local edits, so the change is picked up on the next sync.
+    fn update<F>(&mut self, id: &str, f: F) -> RmkFsResult<Metadata>
+    where
+        F: FnOnce(&mut Metadata),
+    {
+        let mut metadata = self
+            .data
+            .get(id)
+            .cloned()
+            .ok_or_else(|| RmkFsError::NotFound(id.to_string()))?;
+
+        f(&mut metadata);
+        metadata.last_modified = SystemTime::now();
+        metadata._version += 1;
+        metadata._metadatamodified = true;
+
+        write_metadata_with_id(&self.root, id, &metadata)?;
+        self.data.insert(id.to_string(), metadata.clone());
+        self.stat(id);
+        self.hierarchy = Hierarchy::build(&self.data);
+
+        Ok(metadata)
+    }
+
+    fn create_collection(&mut self, parent: &str, name: &str) -> RmkFsResult<(String, Metadata)> {
+        let (id, metadata) = rmk_notebook::create_collection(&self.root, parent, name)?;
+        self.data.insert(id.clone(), metadata.clone());
+        self.contents.insert(id.clone(), Content::default());
+        self.stat(&id);
+        self.hierarchy = Hierarchy::build(&self.data);
+
+        Ok((id, metadata))
+    }
+
+    fn move_to(&mut self, id: &str, parent: &str, name: &str) -> RmkFsResult<Metadata> {
+        self.update(id, |metadata| {
+            metadata.parent = parent.to_string();
+            metadata.visible_name = name.to_string();
+
+            if parent != TRASH_ID {
+                metadata._deleted = false;
+            }
+        })
+    }
+
+    fn remove(&mut self, id: &str) -> RmkFsResult<Metadata> {
+        self.update(id, |metadata| {
+            if metadata.parent == TRASH_ID {
+                metadata._deleted = true;
+            } else {
+                metadata.parent = TRASH_ID.to_string();
+            }
+        })
+    }
+
+    /// Entry listed as `name` under `parent`.
+    fn child(&self, parent: &str, name: &str) -> RmkFsResult<Entry> {
+        self.hierarchy
+            .children(parent)
+            .iter()
+            .find(|entry| entry.name == name)
+            .cloned()
+            .ok_or_else(|| RmkFsError::NotFound(name.to_string()))
+    }
+
+    /// Trashes `entry`, listed as `name`, if it can replace or be replaced by
+    /// a folder when `directory` is set, or by a document otherwise.
+    fn replace(&mut self, entry: &Entry, name: &str, directory: bool) -> RmkFsResult<()> {
+        match (directory, entry.metadata.is_collection()) {
+            (true, false) => Err(RmkFsError::NotADirectory(name.to_string())),
+            (false, true) => Err(RmkFsError::IsADirectory(name.to_string())),
+            (true, true) if !self.hierarchy.children(&entry.id).is_empty() => {
+                Err(RmkFsError::NotEmpty(name.to_string()))
+            }
+            _ => self.remove(&entry.id).map(|_| ()),
+        }
     }
 }
 
@@ -82,21 +472,203 @@ impl Display for RmkTable {
 }
 
 impl RmkTable {
-    pub fn new(root: &PathBuf) -> Self {
+    pub fn new(root: &Path) -> Self {
         Self {
             schema: SchemaRef::new(Schema::new(vec![
                 Field::new("id", DataType::Utf8, false),
                 Field::new("type", DataType::Utf8, false),
                 Field::new("name", DataType::Utf8, false),
                 Field::new("parent", DataType::Utf8, true),
+                Field::new("path", DataType::Utf8, false),
+                Field::new(
+                    "last_modified",
+                    DataType::Timestamp(TimeUnit::Millisecond, None),
+                    false,
+                ),
+                Field::new(
+                    "last_opened",
+                    DataType::Timestamp(TimeUnit::Millisecond, None),
+                    true,
+                ),
+                Field::new("version", DataType::UInt64, false),
+                Field::new("pinned", DataType::Boolean, false),
+                Field::new("deleted", DataType::Boolean, false),
+                Field::new("synced", DataType::Boolean, false),
+                Field::new("file_type", DataType::Utf8, true),
+                Field::new("page_count", DataType::UInt64, true),
+                Field::new("orientation", DataType::Utf8, true),
+                Field::new("size_in_bytes", DataType::UInt64, true),
+                Field::new(
+                    "tags",
+                    DataType::List(Box::new(Field::new("item", DataType::Utf8, true))),
+                    false,
+                ),
             ])),
-            inner: Arc::new(RwLock::new(RmkTableInner::new(root.clone()))),
+            inner: Arc::new(RwLock::new(RmkTableInner::new(root.to_path_buf()))),
         }
     }
 
-    pub fn scan(&self) -> RmkFsResult<()> {
+    /// Brings the table up to date with the root folder. Only metadata files
+    /// modified since the previous scan are read again, and those that fail
+    /// to read are logged.
+    pub fn scan(&self) -> RmkFsResult<ScanReport> {
         self.inner.write().unwrap().scan()
     }
+
+    pub fn reload(&self, id: &str) -> RmkFsResult<(Option<Metadata>, Option<Metadata>)> {
+        self.inner.write().unwrap().reload(id)
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.inner.read().unwrap().root.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Metadata> {
+        self.inner.read().unwrap().data.get(id).cloned()
+    }
+
+    /// When the metadata file of `id` was last written.
+    pub fn changed(&self, id: &str) -> Option<SystemTime> {
+        self.inner.read().unwrap().mtimes.get(id).copied()
+    }
+
+    pub fn content(&self, id: &str) -> RmkFsResult<Content> {
+        if let Some(content) = self.inner.read().unwrap().contents.get(id) {
+            return Ok(content.clone());
+        }
+
+        Ok(read_content_with_id(&self.root(), id)?)
+    }
+
+    /// Every document, folders excluded.
+    pub fn documents(&self) -> Vec<(String, Metadata)> {
+        self.inner
+            .read()
+            .unwrap()
+            .data
+            .iter()
+            .filter(|(_, metadata)| !metadata.is_collection())
+            .map(|(id, metadata)| (id.clone(), metadata.clone()))
+            .collect()
+    }
+
+    /// Documents and folders directly under `parent`, named uniquely and
+    /// sorted by name. Deleted ones are only listed under [`TRASH_ID`],
+    /// together with the ones moved to the trash.
+    pub fn children(&self, parent: &str) -> Vec<Entry> {
+        self.inner
+            .read()
+            .unwrap()
+            .hierarchy
+            .children(parent)
+            .to_vec()
+    }
+
+    /// Folder `id` is listed in, see [`RmkTable::children`].
+    pub fn parent(&self, id: &str) -> Option<String> {
+        let inner = self.inner.read().unwrap();
+        inner.hierarchy.parent(id).map(str::to_string)
+    }
+
+    pub fn path(&self, id: &str) -> Option<String> {
+        let inner = self.inner.read().unwrap();
+        inner.hierarchy.path(id).map(str::to_string)
+    }
+
+    /// Document or folder at `path`, see [`RmkTable::path`].
+    pub fn find(&self, path: &str) -> Option<String> {
+        let inner = self.inner.read().unwrap();
+        inner.hierarchy.find(path).map(str::to_string)
+    }
+
+    /// Whether `id` is `node` itself or one of its folders.
+    pub fn is_ancestor(&self, id: &str, node: &str) -> bool {
+        self.inner.read().unwrap().hierarchy.is_ancestor(id, node)
+    }
+
+    /// Orphans and cycles found in the folder tree.
+    pub fn issues(&self) -> Vec<(String, Issue)> {
+        self.inner.read().unwrap().hierarchy.issues().to_vec()
+    }
+
+    /// Moves `id` under `parent` as `name`. Moving a document out of the
+    /// trash restores it.
+    pub fn move_to(&self, id: &str, parent: &str, name: &str) -> RmkFsResult<Metadata> {
+        self.inner.write().unwrap().move_to(id, parent, name)
+    }
+
+    /// Moves `id` to the trash, or flags it as deleted when it already is
+    /// there. Document data is never removed from disk.
+    pub fn remove(&self, id: &str) -> RmkFsResult<Metadata> {
+        self.inner.write().unwrap().remove(id)
+    }
+
+    pub fn create_collection(&self, parent: &str, name: &str) -> RmkFsResult<(String, Metadata)> {
+        self.inner.write().unwrap().create_collection(parent, name)
+    }
+
+    // The operations below take names as listed by `RmkTable::children` and
+    // check them under the same lock as the change, so that concurrent calls
+    // cannot both succeed.
+
+    /// Creates a folder listed as `name` under `parent`.
+    pub fn mkdir(&self, parent: &str, name: &str) -> RmkFsResult<(String, Metadata)> {
+        let mut inner = self.inner.write().unwrap();
+        if is_reserved(parent, name) || inner.child(parent, name).is_ok() {
+            return Err(RmkFsError::AlreadyExists(name.to_string()));
+        }
+
+        inner.create_collection(parent, &decode(name))
+    }
+
+    /// Removes the folder or document listed as `name` under `parent`, see
+    /// [`RmkTable::remove`]. Only empty folders are removed, and only when
+    /// `directory` is set.
+    pub fn unlink(&self, parent: &str, name: &str, directory: bool) -> RmkFsResult<()> {
+        let mut inner = self.inner.write().unwrap();
+        let entry = inner.child(parent, name)?;
+
+        // Deleted documents are only waiting for the next sync to go away
+        if entry.metadata._deleted {
+            return Err(RmkFsError::PermissionDenied(name.to_string()));
+        }
+
+        inner.replace(&entry, name, directory)
+    }
+
+    /// Moves the entry listed as `name` under `parent` to `new_parent`, to be
+    /// listed as `new_name`. Like rename(2), an existing target is replaced,
+    /// here by trashing it.
+    pub fn rename(
+        &self,
+        parent: &str,
+        name: &str,
+        new_parent: &str,
+        new_name: &str,
+    ) -> RmkFsResult<Metadata> {
+        if is_reserved(new_parent, new_name) {
+            return Err(RmkFsError::PermissionDenied(new_name.to_string()));
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        let entry = inner.child(parent, name)?;
+
+        if inner.hierarchy.is_ancestor(&entry.id, new_parent) {
+            return Err(RmkFsError::InvalidMove { id: entry.id });
+        }
+
+        if let Ok(existing) = inner.child(new_parent, new_name) {
+            if existing.id != entry.id {
+                inner.replace(&existing, new_name, entry.metadata.is_collection())?;
+            }
+        }
+
+        inner.move_to(
+            &entry.id,
+            new_parent,
+            &visible_name(&entry.metadata, new_name),
+        )
+    }
 }
 
 #[async_trait]
@@ -109,15 +681,27 @@ impl TableProvider for RmkTable {
         self.schema.clone()
     }
 
+    fn supports_filter_pushdown(
+        &self,
+        filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        match pushed_down(filter) {
+            Some(_) => Ok(TableProviderFilterPushDown::Exact),
+            None => Ok(TableProviderFilterPushDown::Unsupported),
+        }
+    }
+
     async fn scan(
         &self,
         projection: &Option<Vec<usize>>,
-        _filters: &[datafusion::logical_plan::Expr],
-        _limit: Option<usize>,
+        filters: &[Expr],
+        limit: Option<usize>,
     ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
         Ok(Arc::new(FsExecPlan::try_new(
             self.clone(),
             projection.clone(),
+            filters.iter().filter_map(pushed_down).collect(),
+            limit,
         )?))
     }
 }
@@ -126,17 +710,24 @@ impl TableProvider for RmkTable {
 struct FsExecPlan {
     table: RmkTable,
     projected_schema: SchemaRef,
-    projection: Option<Vec<usize>>,
+    filters: Vec<(&'static str, String)>,
+    limit: Option<usize>,
 }
 
 impl FsExecPlan {
-    fn try_new(table: RmkTable, projection: Option<Vec<usize>>) -> Result<Self, DataFusionError> {
+    fn try_new(
+        table: RmkTable,
+        projection: Option<Vec<usize>>,
+        filters: Vec<(&'static str, String)>,
+        limit: Option<usize>,
+    ) -> Result<Self, DataFusionError> {
         let projected_schema = project_schema(&table.schema(), projection.as_ref())?;
 
         Ok(Self {
             table,
             projected_schema,
-            projection,
+            filters,
+            limit,
         })
     }
 }
@@ -171,9 +762,15 @@ impl ExecutionPlan for FsExecPlan {
 
     fn with_new_children(
         &self,
-        _children: Vec<Arc<dyn ExecutionPlan>>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
-        todo!()
+        if children.is_empty() {
+            Ok(Arc::new(self.clone()))
+        } else {
+            Err(DataFusionError::Internal(
+                "FsExecPlan has no children".to_string(),
+            ))
+        }
     }
 
     async fn execute(
@@ -181,39 +778,353 @@ impl ExecutionPlan for FsExecPlan {
         _partition: usize,
         _runtime: Arc<datafusion::execution::runtime_env::RuntimeEnv>,
     ) -> Result<SendableRecordBatchStream, DataFusionError> {
-        let nodes = {
-            let table = self.table.inner.read().unwrap();
-            table.data.clone()
-        };
-
-        let mut id_array = StringBuilder::new(nodes.len());
-        let mut type_array = StringBuilder::new(nodes.len());
-        let mut name_array = StringBuilder::new(nodes.len());
-        let mut parent_array = StringBuilder::new(nodes.len());
+        let table = self.table.inner.read().unwrap();
+        let rows: Vec<Row> = table.matching(&self.filters, self.limit).collect();
 
-        for (id, metadata) in nodes {
-            id_array.append_value(&id)?;
-            type_array.append_value(&metadata.typ)?;
-            name_array.append_value(&metadata.visible_name)?;
-            parent_array.append_value(&metadata.parent)?;
-        }
+        // Only projected columns are built, paths in particular are looked up
+        // for each row
+        let columns = self
+            .projected_schema
+            .fields()
+            .iter()
+            .map(|field| table.column(field.name(), &rows))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Box::pin(MemoryStream::try_new(
-            vec![RecordBatch::try_new(
-                self.projected_schema.clone(),
-                vec![
-                    Arc::new(id_array.finish()),
-                    Arc::new(type_array.finish()),
-                    Arc::new(name_array.finish()),
-                    Arc::new(parent_array.finish()),
-                ],
-            )?],
+            vec![RecordBatch::try_new(self.schema(), columns)?],
             self.schema(),
-            self.projection.clone(),
+            None,
         )?))
     }
 
-    fn statistics(&self) -> datafusion::physical_plan::Statistics {
-        todo!()
+    /// Row count and an estimate of the size of the text and fixed width
+    /// columns. Not exact: the table can be rescanned before execution.
+    fn statistics(&self) -> Statistics {
+        let table = self.table.inner.read().unwrap();
+
+        let (num_rows, total_byte_size) = table.matching(&self.filters, self.limit).fold(
+            (0, 0),
+            |(rows, bytes), (id, metadata)| {
+                let text = id.len()
+                    + metadata.typ.len()
+                    + metadata.visible_name.len()
+                    + metadata.parent.len();
+
+                (rows + 1, bytes + text + FIXED_ROW_BYTES)
+            },
+        );
+
+        Statistics {
+            num_rows: Some(num_rows),
+            total_byte_size: Some(total_byte_size),
+            column_statistics: None,
+            is_exact: false,
+        }
+    }
+}
+
+/// Registers the tables of the library at `root` in `context`: `metadata`,
+/// and the `pages` and `strokes` computed from it. Returns the metadata
+/// table, which is not scanned yet.
+pub fn register_tables(
+    context: &mut ExecutionContext,
+    root: &Path,
+) -> Result<RmkTable, DataFusionError> {
+    let table = RmkTable::new(root);
+
+    context.register_table("metadata", Arc::new(table.clone()))?;
+    context.register_table("pages", Arc::new(PagesTable::new(table.clone())))?;
+    context.register_table("strokes", Arc::new(StrokesTable::new(table.clone())))?;
+
+    Ok(table)
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use arrow::{
+        array::{
+            Array, BooleanArray, ListArray, StringArray, TimestampMillisecondArray, UInt64Array,
+        },
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        datasource::TableProvider, physical_plan::collect, prelude::ExecutionContext,
+    };
+    use rmk_notebook::generate::{generate, GeneratorConfig};
+
+    use super::{register_tables, RmkTable};
+    use crate::errors::RmkFsResult;
+
+    const SAMPLE_ID: &str = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+    fn context() -> RmkFsResult<(RmkTable, ExecutionContext)> {
+        let mut ctx = ExecutionContext::new();
+        let table = register_tables(&mut ctx, &PathBuf::from("../rmk-notebook/samples"))?;
+        table.scan()?;
+
+        Ok((table, ctx))
+    }
+
+    async fn sql(ctx: &mut ExecutionContext, query: &str) -> RmkFsResult<Vec<RecordBatch>> {
+        Ok(ctx.sql(query).await?.collect().await?)
+    }
+
+    fn strings(batches: &[RecordBatch], column: usize) -> Vec<String> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                let array = batch.column(column);
+                let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+                (0..array.len())
+                    .map(|i| array.value(i).to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn projection_and_filters() -> RmkFsResult<()> {
+        let (_, mut ctx) = context()?;
+
+        let query = format!("SELECT name FROM metadata WHERE id = '{}'", SAMPLE_ID);
+        let batches = sql(&mut ctx, &query).await?;
+        assert_eq!(strings(&batches, 0), vec!["Hedged shared class"]);
+
+        let batches = sql(&mut ctx, "SELECT id FROM metadata WHERE type = 'nope'").await?;
+        assert!(strings(&batches, 0).is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn statistics() -> RmkFsResult<()> {
+        let (table, _) = context()?;
+
+        let plan = TableProvider::scan(&table, &None, &[], None).await?;
+        let statistics = plan.statistics();
+        assert_eq!(statistics.num_rows, Some(1));
+        assert!(statistics.total_byte_size.unwrap() > 0);
+
+        let plan = plan.with_new_children(vec![])?;
+        assert_eq!(plan.statistics().num_rows, Some(1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_projected_columns() -> RmkFsResult<()> {
+        let (table, mut ctx) = context()?;
+
+        let plan = TableProvider::scan(&table, &Some(vec![4, 0]), &[], None).await?;
+        let batches = collect(plan, ctx.runtime_env()).await?;
+        assert_eq!(batches[0].num_columns(), 2);
+        assert_eq!(batches[0].schema().field(0).name(), "path");
+        assert_eq!(strings(&batches, 0), vec!["/Hedged shared class"]);
+        assert_eq!(strings(&batches, 1), vec![SAMPLE_ID]);
+
+        let batches = sql(&mut ctx, "SELECT COUNT(*) FROM metadata").await?;
+        let count = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(count.value(0), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn typed_columns() -> RmkFsResult<()> {
+        let root = std::env::temp_dir().join(format!("rmk-columns-{}", std::process::id()));
+        crate::harness::copy_dir(&PathBuf::from("../rmk-notebook/samples"), &root)?;
+
+        let content = root.join(format!("{}.content", SAMPLE_ID));
+        let mut json: serde_json::Value = serde_json::from_slice(&std::fs::read(&content)?)?;
+        json["tags"] = serde_json::json!([{ "name": "review" }, { "name": "urgent" }]);
+        std::fs::write(&content, serde_json::to_vec(&json)?)?;
+
+        let table = RmkTable::new(&root);
+        table.scan()?;
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("metadata", Arc::new(table))?;
+
+        let batches = sql(
+            &mut ctx,
+            "SELECT last_modified, last_opened, version, pinned, deleted, synced, \
+             file_type, page_count, orientation, size_in_bytes, tags FROM metadata",
+        )
+        .await?;
+        let batch = &batches[0];
+        let column = |index: usize| batch.column(index).as_any();
+
+        let timestamps = |index: usize| {
+            column(index)
+                .downcast_ref::<TimestampMillisecondArray>()
+                .unwrap()
+                .value(0)
+        };
+        assert_eq!(timestamps(0), 1638548277985);
+        assert_eq!(timestamps(1), 1638547442903);
+
+        let numbers = |index: usize| {
+            column(index)
+                .downcast_ref::<UInt64Array>()
+                .unwrap()
+                .value(0)
+        };
+        assert_eq!(numbers(2), 14);
+        assert_eq!(numbers(7), 6);
+        assert_eq!(numbers(9), 3985418);
+
+        let booleans = |index: usize| {
+            column(index)
+                .downcast_ref::<BooleanArray>()
+                .unwrap()
+                .value(0)
+        };
+        assert!(!booleans(3));
+        assert!(!booleans(4));
+        assert!(booleans(5));
+
+        assert_eq!(strings(&batches, 6), vec!["notebook"]);
+        assert_eq!(strings(&batches, 8), vec!["portrait"]);
+
+        let tags = column(10).downcast_ref::<ListArray>().unwrap().value(0);
+        let tags = tags.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            tags.iter().flatten().collect::<Vec<_>>(),
+            vec!["review", "urgent"]
+        );
+
+        // Typed columns compare as such
+        let batches = sql(
+            &mut ctx,
+            "SELECT id FROM metadata WHERE NOT pinned AND version > 9 \
+             AND page_count >= 6 AND last_modified > CAST('2021-12-01T00:00:00' AS TIMESTAMP)",
+        )
+        .await?;
+        assert_eq!(strings(&batches, 0), vec![SAMPLE_ID]);
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aggregate_and_join() -> RmkFsResult<()> {
+        let (_, mut ctx) = context()?;
+
+        let batches = sql(
+            &mut ctx,
+            "SELECT type, COUNT(*) FROM metadata GROUP BY type ORDER BY type",
+        )
+        .await?;
+        assert_eq!(strings(&batches, 0), vec!["DocumentType"]);
+
+        let batches = sql(
+            &mut ctx,
+            "SELECT m.name, COUNT(p.page_id) AS pages \
+             FROM metadata m JOIN pages p ON m.id = p.document_id \
+             GROUP BY m.name",
+        )
+        .await?;
+        let pages = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(pages.value(0), 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn explain() -> RmkFsResult<()> {
+        let (_, mut ctx) = context()?;
+
+        let batches = sql(
+            &mut ctx,
+            "EXPLAIN SELECT name FROM metadata WHERE parent = ''",
+        )
+        .await?;
+        assert!(batches.iter().map(|batch| batch.num_rows()).sum::<usize>() > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rescan_after_changes() -> RmkFsResult<()> {
+        let root = std::env::temp_dir().join(format!("rmk-rescan-{}", std::process::id()));
+        crate::harness::copy_dir(&PathBuf::from("../rmk-notebook/samples"), &root)?;
+
+        let table = RmkTable::new(&root);
+        assert_eq!(table.scan()?.added, vec![SAMPLE_ID.to_string()]);
+        assert!(table.scan()?.is_empty());
+
+        let metadata = |id: &str| root.join(format!("{}.metadata", id));
+        let copy = "00000000-0000-0000-0000-000000000000";
+        std::fs::copy(metadata(SAMPLE_ID), metadata(copy))?;
+        let bumped = std::fs::read_to_string(metadata(SAMPLE_ID))?
+            .replace("\"version\": 14", "\"version\": 15");
+        std::fs::write(metadata(SAMPLE_ID), bumped)?;
+        std::fs::write(metadata("broken"), "{")?;
+
+        let report = table.scan()?;
+        assert_eq!(report.added, vec![copy.to_string()]);
+        assert_eq!(report.changed, vec![SAMPLE_ID.to_string()]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, metadata("broken"));
+        assert_eq!(table.get(SAMPLE_ID).unwrap()._version, 15);
+
+        std::fs::remove_file(metadata(copy))?;
+        std::fs::remove_file(metadata("broken"))?;
+        let report = table.scan()?;
+        assert_eq!(report.removed, vec![copy.to_string()]);
+        assert!(report.failed.is_empty());
+        assert!(table.get(copy).is_none());
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generated_library() -> RmkFsResult<()> {
+        let root = std::env::temp_dir().join(format!("rmk-table-{}", std::process::id()));
+        let config = GeneratorConfig::with_documents(200);
+        let library = generate(&root, &config)?;
+
+        let table = RmkTable::new(&root);
+        let report = table.scan()?;
+        assert_eq!(
+            report.added.len(),
+            library.folders.len() + library.documents.len()
+        );
+        assert!(report.failed.is_empty());
+        assert!(table.issues().is_empty());
+
+        let mut ctx = ExecutionContext::new();
+        ctx.register_table("metadata", Arc::new(table.clone()))?;
+
+        let batches = sql(&mut ctx, "SELECT id FROM metadata WHERE file_type = 'epub'").await?;
+        assert_eq!(strings(&batches, 0).len(), config.epubs);
+
+        let batches = sql(
+            &mut ctx,
+            "SELECT path FROM metadata WHERE deleted OR parent = 'trash'",
+        )
+        .await?;
+        assert!(strings(&batches, 0)
+            .iter()
+            .all(|path| path.starts_with("/trash/")));
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
     }
 }
@@ -0,0 +1,96 @@
+use std::{
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use fuser::Notifier;
+use log::{debug, info, warn};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{errors::RmkFsResult, fs::RmkFs};
+
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Quiet time after which a sync is considered over and snapshotted.
+const SETTLE: Duration = Duration::from_secs(5);
+
+const WATCHED_EXTENSIONS: &[&str] = &["metadata", "content", "pagedata", "pdf", "epub", "rm"];
+
+/// Keeps the mount in sync with the xochitl root for as long as it is alive.
+pub struct RootWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl RootWatcher {
+    pub fn spawn(fs: RmkFs, notifier: Notifier) -> RmkFsResult<Self> {
+        let root = fs.root();
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher = notify::watcher(tx, DEBOUNCE)?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        info!("Watching {}", root.display());
+
+        // Ends when the watcher, and with it the sender, is dropped
+        thread::spawn(move || {
+            let mut changed = false;
+
+            loop {
+                let event = match rx.recv_timeout(SETTLE) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => {
+                        if changed {
+                            fs.take_snapshot();
+                            changed = false;
+                        }
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                debug!("{:?}", event);
+
+                match event {
+                    DebouncedEvent::Create(path)
+                    | DebouncedEvent::Write(path)
+                    | DebouncedEvent::Remove(path) => {
+                        reload(&fs, &notifier, &root, &path);
+                        changed = true;
+                    }
+                    DebouncedEvent::Rename(from, to) => {
+                        reload(&fs, &notifier, &root, &from);
+                        reload(&fs, &notifier, &root, &to);
+                        changed = true;
+                    }
+                    DebouncedEvent::Rescan => fs.rescan(),
+                    DebouncedEvent::Error(e, path) => warn!("Watch error on {:?}: {}", path, e),
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(RootWatcher { _watcher: watcher })
+    }
+}
+
+fn reload(fs: &RmkFs, notifier: &Notifier, root: &Path, path: &Path) {
+    let watched = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| WATCHED_EXTENSIONS.contains(&ext));
+
+    if let (true, Some(id)) = (watched, document_id(root, path)) {
+        fs.reload(&id, notifier);
+    }
+}
+
+/// Document a changed file belongs to: `<id>.<ext>` at the root of the
+/// xochitl folder, or anything below `<id>/`.
+fn document_id(root: &Path, path: &Path) -> Option<String> {
+    let first = path.strip_prefix(root).ok()?.components().next()?;
+    let first = first.as_os_str().to_str()?;
+
+    first.split('.').next().map(|id| id.to_string())
+}
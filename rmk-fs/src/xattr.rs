@@ -0,0 +1,34 @@
+use rmk_notebook::{Content, Metadata};
+
+pub const XATTR_PREFIX: &str = "user.rmk.";
+
+/// Extended attributes of a document, in the order `listxattr` reports them.
+///
+/// Values are plain text so they can be read with `getfattr`/`xattr`
+/// without decoding; `tags` is a comma separated list.
+pub fn xattrs(id: &str, metadata: &Metadata, content: Option<&Content>) -> Vec<(String, Vec<u8>)> {
+    let mut attrs = vec![
+        ("id", id.to_string()),
+        ("version", metadata._version.to_string()),
+        ("pinned", metadata._pinned.to_string()),
+        ("synced", metadata._synced.to_string()),
+    ];
+
+    if let Some(content) = content {
+        if !metadata.is_collection() {
+            attrs.push(("page_count", content.page_count.to_string()));
+        }
+
+        if let Some(file_type) = &content.file_type {
+            attrs.push(("file_type", file_type.clone()));
+        }
+
+        let tags: Vec<&str> = content.tags.iter().map(|tag| tag.name.as_str()).collect();
+        attrs.push(("tags", tags.join(",")));
+    }
+
+    attrs
+        .into_iter()
+        .map(|(name, value)| (format!("{}{}", XATTR_PREFIX, name), value.into_bytes()))
+        .collect()
+}
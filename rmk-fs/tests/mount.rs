@@ -0,0 +1,45 @@
+//! Mounts the sample xochitl root under the temporary directory. Skipped when
+//! `/dev/fuse` is missing or mounting is not permitted, e.g. in containers.
+
+use std::{fs, path::PathBuf};
+
+use rmk_fs::RmkFs;
+
+#[test]
+fn mount_list_and_read() {
+    if !PathBuf::from("/dev/fuse").exists() {
+        eprintln!("/dev/fuse not found, skipping");
+        return;
+    }
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+
+    let mountpoint = std::env::temp_dir().join(format!("rmk-mount-{}", std::process::id()));
+    fs::create_dir_all(&mountpoint).unwrap();
+
+    let rmk = RmkFs::try_new(&PathBuf::from("../rmk-notebook/samples")).unwrap();
+    let mount = match rmk.mount(mountpoint.to_str().unwrap()) {
+        Ok(mount) => mount,
+        Err(e) => {
+            eprintln!("cannot mount, skipping: {}", e);
+            fs::remove_dir(&mountpoint).ok();
+            return;
+        }
+    };
+
+    let names: Vec<String> = fs::read_dir(&mountpoint)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.contains(&"Hedged shared class.pdf".to_string()));
+
+    let pdf = fs::read(mountpoint.join("Hedged shared class.pdf")).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+
+    let error = fs::read(mountpoint.join("missing.pdf")).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+
+    drop(mount);
+    fs::remove_dir(&mountpoint).ok();
+}
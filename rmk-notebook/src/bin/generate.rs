@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use rmk_notebook::generate::{generate, GeneratorConfig};
+use rmk_notebook::Result;
+
+const USAGE: &str = "usage: generate <root> [--documents N] [--seed S]";
+
+fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let mut root = None;
+    let mut documents = 100;
+    let mut seed = 0;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--documents" => documents = number(args.next()),
+            "--seed" => seed = number(args.next()) as u64,
+            _ if root.is_none() && !arg.starts_with('-') => root = Some(PathBuf::from(arg)),
+            _ => usage(),
+        }
+    }
+
+    let root = root.unwrap_or_else(|| usage());
+    let config = GeneratorConfig {
+        seed,
+        ..GeneratorConfig::with_documents(documents)
+    };
+
+    let library = generate(&root, &config)?;
+    println!(
+        "{} folders and {} documents written to {}",
+        library.folders.len(),
+        library.documents.len(),
+        root.display()
+    );
+
+    Ok(())
+}
+
+fn number(arg: Option<String>) -> usize {
+    arg.and_then(|arg| arg.parse().ok())
+        .unwrap_or_else(|| usage())
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2)
+}
@@ -0,0 +1,343 @@
+//! Page and stroke differences between two versions of a notebook.
+//!
+//! Pages are matched by id, so reordered pages are not reported as changed.
+//! Strokes are matched by brush, color and points: xochitl never edits a
+//! stroke in place, erasing part of one replaces it by what is left.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use crate::{
+    image::write_svg,
+    render::{render_line, render_pages, stroke_color},
+    rm::{Line, Page},
+    Error, Notebook, Result,
+};
+
+const UNCHANGED_COLOR: [f32; 3] = [0.75, 0.75, 0.75];
+const ADDED_COLOR: [f32; 3] = [0.1, 0.65, 0.2];
+const REMOVED_COLOR: [f32; 3] = [0.85, 0.1, 0.1];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+    Unchanged,
+}
+
+#[derive(Debug, Clone)]
+pub struct PageDiff {
+    pub page_id: String,
+    /// Position in the old notebook, `None` for an added page
+    pub old_index: Option<usize>,
+    /// Position in the new notebook, `None` for a removed page
+    pub new_index: Option<usize>,
+    pub change: Change,
+    /// Strokes only in the new page, as indexes in [`Page::lines`]
+    pub added: Vec<usize>,
+    /// Strokes only in the old page, as indexes in [`Page::lines`]
+    pub removed: Vec<usize>,
+    pub unchanged: usize,
+}
+
+/// Pages of both versions, in the order of the new one with removed pages
+/// where they used to be.
+#[derive(Debug, Clone, Default)]
+pub struct NotebookDiff {
+    pub pages: Vec<PageDiff>,
+}
+
+pub fn diff(old: &Notebook, new: &Notebook) -> NotebookDiff {
+    let old_indexes: HashMap<&str, usize> = old
+        .content
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (id.as_str(), index))
+        .collect();
+    let new_ids: Vec<&str> = new.content.pages.iter().map(String::as_str).collect();
+    let kept: HashSet<&str> = new_ids.iter().copied().collect();
+
+    let mut removed = old
+        .content
+        .pages
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| !kept.contains(id.as_str()))
+        .peekable();
+
+    let mut pages = Vec::with_capacity(new_ids.len());
+
+    for (new_index, id) in new_ids.iter().enumerate() {
+        let old_index = old_indexes.get(id).copied();
+
+        if let Some(old_index) = old_index {
+            while let Some((index, removed_id)) = removed.next_if(|(index, _)| *index < old_index) {
+                pages.push(page_diff(removed_id, Some(index), None, old, new));
+            }
+        }
+
+        pages.push(page_diff(id, old_index, Some(new_index), old, new));
+    }
+
+    for (index, removed_id) in removed {
+        pages.push(page_diff(removed_id, Some(index), None, old, new));
+    }
+
+    NotebookDiff { pages }
+}
+
+fn page_diff(
+    page_id: &str,
+    old_index: Option<usize>,
+    new_index: Option<usize>,
+    old: &Notebook,
+    new: &Notebook,
+) -> PageDiff {
+    let blank = Page::default();
+    let old_page = old_index.and_then(|i| old.pages.get(i)).unwrap_or(&blank);
+    let new_page = new_index.and_then(|i| new.pages.get(i)).unwrap_or(&blank);
+
+    let mut old_lines: HashMap<u64, VecDeque<usize>> = HashMap::new();
+    for (index, line) in old_page.lines().enumerate() {
+        old_lines
+            .entry(fingerprint(line))
+            .or_default()
+            .push_back(index);
+    }
+
+    let mut added = Vec::new();
+    let mut unchanged = 0;
+    for (index, line) in new_page.lines().enumerate() {
+        match old_lines
+            .get_mut(&fingerprint(line))
+            .and_then(VecDeque::pop_front)
+        {
+            Some(_) => unchanged += 1,
+            None => added.push(index),
+        }
+    }
+
+    let mut removed: Vec<usize> = old_lines.into_values().flatten().collect();
+    removed.sort_unstable();
+
+    let change = match (old_index, new_index) {
+        (None, _) => Change::Added,
+        (_, None) => Change::Removed,
+        _ if added.is_empty() && removed.is_empty() => Change::Unchanged,
+        _ => Change::Modified,
+    };
+
+    PageDiff {
+        page_id: page_id.to_string(),
+        old_index,
+        new_index,
+        change,
+        added,
+        removed,
+        unchanged,
+    }
+}
+
+/// Identifies a stroke by everything drawn, speed and pressure aside.
+fn fingerprint(line: &Line) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    line.brush_type.hash(&mut hasher);
+    line.color.hash(&mut hasher);
+    line.brush_base_size.to_bits().hash(&mut hasher);
+    for point in &line.points {
+        point.x.to_bits().hash(&mut hasher);
+        point.y.to_bits().hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+impl NotebookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.pages
+            .iter()
+            .all(|page| page.change == Change::Unchanged)
+    }
+
+    /// Pages with changes, see [`NotebookDiff::pages`] for every page.
+    pub fn changes(&self) -> impl Iterator<Item = &PageDiff> {
+        self.pages
+            .iter()
+            .filter(|page| page.change != Change::Unchanged)
+    }
+
+    /// Writes a PDF of every page of the diff: strokes in common in light
+    /// grey, additions in green and removals in red.
+    pub fn render<W: Write>(&self, old: &Notebook, new: &Notebook, target: &mut W) -> Result<()> {
+        let pages = self
+            .pages
+            .iter()
+            .map(|page| {
+                let mut operations = vec![];
+                let mut color = None;
+
+                for (line, rgb) in page.strokes(old, new) {
+                    if color != Some(rgb) {
+                        stroke_color(rgb, &mut operations);
+                        color = Some(rgb);
+                    }
+                    render_line(line, &mut operations);
+                }
+
+                operations
+            })
+            .collect();
+
+        render_pages(pages, target)
+    }
+
+    /// Writes page `page` of the diff as an SVG document, in the colors of
+    /// [`NotebookDiff::render`].
+    pub fn render_svg<W: Write>(
+        &self,
+        page: usize,
+        old: &Notebook,
+        new: &Notebook,
+        target: &mut W,
+    ) -> Result<()> {
+        let diff = self
+            .pages
+            .get(page)
+            .ok_or_else(|| Error::InvalidPages(format!("page {} of {}", page, self.pages.len())))?;
+
+        let strokes = diff.strokes(old, new).into_iter().map(|(line, rgb)| {
+            let rgb = rgb.map(|component| (component * 255.).round() as u8);
+            (line, Some(rgb))
+        });
+
+        write_svg(strokes, target)
+    }
+}
+
+impl PageDiff {
+    /// Strokes of both versions in drawing order, with their color: those in
+    /// common first, then removals and additions on top.
+    fn strokes<'a>(&self, old: &'a Notebook, new: &'a Notebook) -> Vec<(&'a Line, [f32; 3])> {
+        let old_lines: Vec<&Line> = self
+            .old_index
+            .and_then(|i| old.pages.get(i))
+            .map_or_else(Vec::new, |page| page.lines().collect());
+        let new_lines: Vec<&Line> = self
+            .new_index
+            .and_then(|i| new.pages.get(i))
+            .map_or_else(Vec::new, |page| page.lines().collect());
+
+        let mut added = vec![false; new_lines.len()];
+        for index in &self.added {
+            added[*index] = true;
+        }
+
+        let unchanged = new_lines
+            .iter()
+            .zip(&added)
+            .filter(|(_, added)| !**added)
+            .map(|(line, _)| (*line, UNCHANGED_COLOR));
+        let removed = self
+            .removed
+            .iter()
+            .map(|index| (old_lines[*index], REMOVED_COLOR));
+        let added = self
+            .added
+            .iter()
+            .map(|index| (new_lines[*index], ADDED_COLOR));
+
+        unchanged.chain(removed).chain(added).collect()
+    }
+}
+
+impl Display for NotebookDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for page in self.changes() {
+            let index = page.new_index.or(page.old_index).unwrap_or_default() + 1;
+
+            match page.change {
+                Change::Added => write!(f, "+ page {}", index)?,
+                Change::Removed => write!(f, "- page {}", index)?,
+                _ => write!(f, "~ page {}", index)?,
+            }
+
+            writeln!(
+                f,
+                " ({}): +{} -{} strokes",
+                page.page_id,
+                page.added.len(),
+                page.removed.len()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{diff, Change};
+    use crate::{read_notebook, Result};
+
+    #[test]
+    fn erased_stroke_and_removed_page() -> Result<()> {
+        let root = PathBuf::from("samples");
+        let id = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+        let old = read_notebook(&root, id)?;
+        let mut new = read_notebook(&root, id)?;
+
+        assert!(diff(&old, &new).is_empty());
+
+        // Erase a stroke of the last page and remove the first one
+        let last = new.pages.len() - 1;
+        let erased = new.pages[last].layers[0].lines.remove(0);
+        new.content.pages.remove(0);
+        new.pages.remove(0);
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.pages.len(), old.pages.len());
+
+        let changed: Vec<_> = changes.changes().collect();
+        assert_eq!(changed.len(), 2);
+
+        assert_eq!(changed[0].change, Change::Removed);
+        assert_eq!(changed[0].page_id, old.content.pages[0]);
+        assert_eq!(changes.pages[0].old_index, Some(0));
+
+        assert_eq!(changed[1].change, Change::Modified);
+        assert_eq!(changed[1].new_index, Some(last - 1));
+        assert_eq!(changed[1].removed, vec![0]);
+        assert!(changed[1].added.is_empty());
+
+        let mut pdf = Vec::new();
+        changes.render(&old, &new, &mut pdf)?;
+        assert!(pdf.starts_with(b"%PDF"));
+
+        // The erased stroke is drawn in red over the others in grey
+        let mut svg = Vec::new();
+        changes.render_svg(changes.pages.len() - 1, &old, &new, &mut svg)?;
+        let svg = String::from_utf8(svg).unwrap();
+        assert_eq!(svg.matches(r##"stroke="#d91a1a""##).count(), 1);
+        assert!(!svg.contains(r##"stroke="#1aa633""##));
+        assert!(svg.contains(r##"stroke="#bfbfbf""##));
+        assert!(changes
+            .render_svg(changes.pages.len(), &old, &new, &mut Vec::new())
+            .is_err());
+
+        // Strokes are matched whatever their order
+        new.pages[last - 1].layers[0].lines.push(erased);
+        assert_eq!(diff(&old, &new).changes().count(), 1);
+
+        Ok(())
+    }
+}
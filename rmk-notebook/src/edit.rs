@@ -0,0 +1,379 @@
+//! Page operations on notebooks, writing the files the way xochitl would so
+//! the result syncs back to the tablet: merging notebooks, extracting pages
+//! into a new one, reordering and deleting pages.
+//!
+//! Page files are copied as is, whatever version of the .rm format they use.
+
+use std::{
+    collections::HashSet,
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    notebook::{
+        read_content_with_id, read_metadata_with_id, read_pagedata_with_id, write_content_with_id,
+        write_json, write_metadata_with_id, write_pagedata_with_id,
+    },
+    Content, Error, Metadata, Result, DOCUMENT_TYPE,
+};
+
+const NOTEBOOK_TYPE: &str = "notebook";
+const BLANK_TEMPLATE: &str = "Blank";
+
+/// Keys of a .content file about its pages or the document as a whole, not
+/// carried over to the notebooks made from it.
+const DOCUMENT_KEYS: &[&str] = &[
+    "cPages",
+    "coverPageNumber",
+    "lastOpenedPage",
+    "originalPageCount",
+    "pageTags",
+    "redirectionPageMap",
+    "sizeInBytes",
+    "tags",
+];
+
+/// A page with its index in the notebook.
+type IndexedPage<'a> = (usize, &'a PageRef);
+
+/// A page of a notebook, located by document and page id.
+struct PageRef {
+    id: String,
+    page: String,
+    template: String,
+}
+
+/// Creates a notebook in folder `parent` made of the pages of `ids`, in
+/// order, and returns its id. The notebooks themselves are left as they are.
+pub fn merge(root: &Path, ids: &[&str], parent: &str, name: &str) -> Result<String> {
+    let mut pages = Vec::new();
+    for id in ids {
+        pages.extend(page_refs(root, id)?);
+    }
+
+    match ids.first() {
+        Some(first) => create(root, first, &pages, parent, name),
+        None => Err(Error::InvalidPages("no notebook to merge".to_string())),
+    }
+}
+
+/// Creates a notebook in folder `parent` with a copy of pages `range` of
+/// `id`, and returns its id.
+pub fn extract(
+    root: &Path,
+    id: &str,
+    range: Range<usize>,
+    parent: &str,
+    name: &str,
+) -> Result<String> {
+    let mut pages = page_refs(root, id)?;
+    if range.is_empty() || range.end > pages.len() {
+        return Err(Error::InvalidPages(format!(
+            "{:?} of {} pages",
+            range,
+            pages.len()
+        )));
+    }
+
+    let pages: Vec<PageRef> = pages.drain(range).collect();
+
+    create(root, id, &pages, parent, name)
+}
+
+/// Puts the pages of `id` in the order of `order`, which lists every page
+/// index once.
+pub fn reorder(root: &Path, id: &str, order: &[usize]) -> Result<()> {
+    let pages = page_refs(root, id)?;
+
+    let unique: HashSet<&usize> = order.iter().collect();
+    if order.len() != pages.len()
+        || unique.len() != pages.len()
+        || order.iter().any(|i| *i >= pages.len())
+    {
+        return Err(Error::InvalidPages(format!(
+            "{:?} is not an order of {} pages",
+            order,
+            pages.len()
+        )));
+    }
+
+    let pages: Vec<&PageRef> = order.iter().map(|i| &pages[*i]).collect();
+    update(root, id, &pages)
+}
+
+/// Removes pages `indexes` of `id` and their files. A notebook keeps at
+/// least one page, like on the tablet.
+pub fn delete_pages(root: &Path, id: &str, indexes: &[usize]) -> Result<()> {
+    let pages = page_refs(root, id)?;
+
+    let deleted: HashSet<usize> = indexes.iter().copied().collect();
+    if deleted.iter().any(|i| *i >= pages.len()) || deleted.len() >= pages.len() {
+        return Err(Error::InvalidPages(format!(
+            "cannot delete {:?} of {} pages",
+            indexes,
+            pages.len()
+        )));
+    }
+
+    let (deleted, kept): (Vec<IndexedPage>, Vec<IndexedPage>) = pages
+        .iter()
+        .enumerate()
+        .partition(|(i, _)| deleted.contains(i));
+
+    let kept: Vec<&PageRef> = kept.into_iter().map(|(_, page)| page).collect();
+    update(root, id, &kept)?;
+
+    for (_, page) in deleted {
+        for file in page_files(root, &page.id, &page.page) {
+            remove_file(&file)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pages of notebook `id`, in order.
+fn page_refs(root: &Path, id: &str) -> Result<Vec<PageRef>> {
+    let content = read_content_with_id(root, id)?;
+    if content.file_type.as_deref() != Some(NOTEBOOK_TYPE) {
+        return Err(Error::UnsupportedDocument(format!(
+            "{} is not a notebook",
+            id
+        )));
+    }
+
+    // Notebooks created before templates were recorded have no .pagedata
+    let templates = read_pagedata_with_id(root, id).unwrap_or_default();
+
+    Ok(content
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| PageRef {
+            id: id.to_string(),
+            page: page.clone(),
+            template: templates
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| BLANK_TEMPLATE.to_string()),
+        })
+        .collect())
+}
+
+/// Files of page `page` of document `id`, whether they exist or not.
+fn page_files(root: &Path, id: &str, page: &str) -> [PathBuf; 3] {
+    [
+        root.join(id).join(format!("{}.rm", page)),
+        root.join(id).join(format!("{}-metadata.json", page)),
+        root.join(format!("{}.thumbnails", id))
+            .join(format!("{}.jpg", page)),
+    ]
+}
+
+/// Creates a notebook with a copy of `pages`, set up like notebook `source`.
+fn create(
+    root: &Path,
+    source: &str,
+    pages: &[PageRef],
+    parent: &str,
+    name: &str,
+) -> Result<String> {
+    let id = Uuid::new_v4().to_string();
+    std::fs::create_dir_all(root.join(&id))?;
+
+    let mut page_ids = Vec::with_capacity(pages.len());
+    for page in pages {
+        let new_page = Uuid::new_v4().to_string();
+
+        let sources = page_files(root, &page.id, &page.page);
+        let targets = page_files(root, &id, &new_page);
+        for (source, target) in sources.iter().zip(&targets) {
+            copy_file(source, target)?;
+        }
+
+        page_ids.push(new_page);
+    }
+
+    let templates: Vec<String> = pages.iter().map(|page| page.template.clone()).collect();
+    write_pagedata_with_id(root, &id, &templates)?;
+
+    // Settings `Content` does not know about, such as extraMetadata or
+    // formatVersion, are kept by writing the fields it knows over them
+    let mut settings: Value =
+        serde_json::from_str(&std::fs::read_to_string(content_path(root, source))?)?;
+    if let Some(fields) = settings.as_object_mut() {
+        fields.retain(|key, _| !DOCUMENT_KEYS.contains(&key.as_str()));
+    }
+    write_json(&content_path(root, &id), &settings)?;
+
+    let content = Content {
+        file_type: Some(NOTEBOOK_TYPE.to_string()),
+        page_count: page_ids.len(),
+        pages: page_ids,
+        orientation: read_content_with_id(root, source)?.orientation,
+        ..Default::default()
+    };
+    write_content_with_id(root, &id, &content)?;
+
+    // Written last, xochitl and RmkFs only pick up documents with metadata
+    let metadata = Metadata::new(DOCUMENT_TYPE, parent, name);
+    write_metadata_with_id(root, &id, &metadata)?;
+
+    Ok(id)
+}
+
+fn content_path(root: &Path, id: &str) -> PathBuf {
+    root.join(format!("{}.content", id))
+}
+
+/// Rewrites the page list of `id` and marks it modified.
+fn update(root: &Path, id: &str, pages: &[&PageRef]) -> Result<()> {
+    let templates: Vec<String> = pages.iter().map(|page| page.template.clone()).collect();
+    write_pagedata_with_id(root, id, &templates)?;
+
+    let mut content = read_content_with_id(root, id)?;
+    content.pages = pages.iter().map(|page| page.page.clone()).collect();
+    content.page_count = content.pages.len();
+    write_content_with_id(root, id, &content)?;
+
+    let mut metadata = read_metadata_with_id(root, id)?;
+    metadata.last_modified = SystemTime::now();
+    metadata._version += 1;
+    metadata._modified = true;
+    metadata._metadatamodified = true;
+    write_metadata_with_id(root, id, &metadata)
+}
+
+/// Copies `source` if it exists, pages never written on have no files.
+fn copy_file(source: &Path, target: &Path) -> Result<()> {
+    if !source.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::copy(source, target)
+        .map(|_| ())
+        .map_err(|e| Error::WriteError(format!("{}: {}", target.display(), e)))
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(Error::WriteError(format!("{}: {}", path.display(), e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{delete_pages, extract, merge, reorder};
+    use crate::{
+        notebook::{read_content_with_id, read_pagedata_with_id},
+        read_metadata_with_id, read_notebook, Error, Result,
+    };
+
+    const SAMPLE_ID: &str = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+    /// A copy of the sample notebook, to be modified.
+    fn sample(test: &str) -> Result<PathBuf> {
+        let root = std::env::temp_dir().join(format!("rmk-edit-{}-{}", test, std::process::id()));
+        let samples = PathBuf::from("samples");
+
+        std::fs::create_dir_all(root.join(SAMPLE_ID))?;
+        for dir in [samples.clone(), samples.join(SAMPLE_ID)] {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    let relative = path.strip_prefix(&samples).unwrap();
+                    std::fs::copy(&path, root.join(relative))?;
+                }
+            }
+        }
+
+        Ok(root)
+    }
+
+    #[test]
+    fn merge_and_extract() -> Result<()> {
+        let root = sample("merge")?;
+        let pages = read_content_with_id(&root, SAMPLE_ID)?.pages;
+
+        let merged = merge(&root, &[SAMPLE_ID, SAMPLE_ID], "", "Merged")?;
+        let content = read_content_with_id(&root, &merged)?;
+        assert_eq!(content.pages.len(), 2 * pages.len());
+        assert!(content.pages.iter().all(|page| !pages.contains(page)));
+
+        let raw = |id: &str| -> Result<serde_json::Value> {
+            let file = std::fs::read_to_string(root.join(format!("{}.content", id)))?;
+            Ok(serde_json::from_str(&file)?)
+        };
+        let (source, copy) = (raw(SAMPLE_ID)?, raw(&merged)?);
+        assert_eq!(copy["extraMetadata"], source["extraMetadata"]);
+        assert_eq!(copy["textScale"], source["textScale"]);
+        assert!(copy.get("redirectionPageMap").is_none());
+        assert_eq!(
+            read_metadata_with_id(&root, &merged)?.visible_name,
+            "Merged"
+        );
+        read_notebook(&root, &merged)?;
+
+        let extracted = extract(&root, SAMPLE_ID, 1..3, "", "Extract")?;
+        let notebook = read_notebook(&root, &extracted)?;
+        assert_eq!(notebook.pages.len(), 2);
+        assert_eq!(read_pagedata_with_id(&root, &extracted)?.len(), 2);
+
+        assert!(matches!(
+            extract(&root, SAMPLE_ID, 4..10, "", "Too far"),
+            Err(Error::InvalidPages(_))
+        ));
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reorder_and_delete() -> Result<()> {
+        let root = sample("reorder")?;
+        let pages = read_content_with_id(&root, SAMPLE_ID)?.pages;
+        let version = read_metadata_with_id(&root, SAMPLE_ID)?._version;
+
+        let order: Vec<usize> = (0..pages.len()).rev().collect();
+        reorder(&root, SAMPLE_ID, &order)?;
+
+        let reordered = read_content_with_id(&root, SAMPLE_ID)?.pages;
+        assert_eq!(reordered.first(), pages.last());
+        assert_eq!(
+            read_metadata_with_id(&root, SAMPLE_ID)?._version,
+            version + 1
+        );
+        assert!(reorder(&root, SAMPLE_ID, &[0, 0, 1, 2, 3, 4]).is_err());
+
+        delete_pages(&root, SAMPLE_ID, &[0])?;
+        let content = read_content_with_id(&root, SAMPLE_ID)?;
+        assert_eq!(content.pages, reordered[1..]);
+        assert_eq!(content.page_count, pages.len() - 1);
+        assert!(!root
+            .join(SAMPLE_ID)
+            .join(format!("{}.rm", reordered[0]))
+            .exists());
+        read_notebook(&root, SAMPLE_ID)?;
+
+        let all: Vec<usize> = (0..content.pages.len()).collect();
+        assert!(delete_pages(&root, SAMPLE_ID, &all).is_err());
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
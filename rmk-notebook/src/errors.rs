@@ -30,6 +30,12 @@ pub enum Error {
 
     #[error("invalid Segment index: {0}")]
     InvalidSegmentIndex(usize),
+
+    #[error("unsupported document: {0}")]
+    UnsupportedDocument(String),
+
+    #[error("invalid pages: {0}")]
+    InvalidPages(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
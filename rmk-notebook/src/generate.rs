@@ -0,0 +1,561 @@
+//! Fake xochitl roots for tests and benchmarks: nested folders, notebooks
+//! with random strokes, PDFs and EPUBs, some of them deleted or in the trash.
+//!
+//! Generation is deterministic for a given [`GeneratorConfig::seed`].
+
+use std::{
+    path::PathBuf,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    notebook::{
+        write_content_with_id, write_metadata_with_id, write_page_with_id, write_pagedata_with_id,
+    },
+    rm::{BrushType, Color, Layer, Line, Page, Point},
+    Content, Metadata, Notebook, Result, COLLECTION_TYPE, DOCUMENT_TYPE,
+};
+
+const TRASH: &str = "trash";
+
+const REMARKABLE_WIDTH: f32 = 1404.;
+const REMARKABLE_HEIGHT: f32 = 1872.;
+
+/// Timestamps are spread over the year before this date, 2022-04-15.
+const NOW: u64 = 1_650_000_000;
+const YEAR: u64 = 365 * 24 * 3600;
+
+const WORDS: &[&str] = &[
+    "Meeting", "Notes", "Project", "Ideas", "Sketches", "Journal", "Budget", "Roadmap", "Review",
+    "Lecture", "Reading", "Draft", "Plan", "Todo", "Research", "Design", "Retro", "Weekly",
+];
+
+/// Names xochitl accepts but file systems do not, to exercise name escaping.
+const ODD_NAMES: &[&str] = &["Q1/Q2 plan", "Ends with a dot.", "50% done", "  "];
+
+const TEMPLATES: &[&str] = &[
+    "Blank",
+    "P Lines medium",
+    "P Grid small",
+    "P Dots S",
+    "LS Checklist",
+];
+
+const BRUSHES: &[BrushType] = &[
+    BrushType::BallPoint,
+    BrushType::Fineliner,
+    BrushType::Marker,
+    BrushType::SharpPencil,
+    BrushType::TiltPencil,
+    BrushType::Highlighter,
+    BrushType::Calligraphy,
+];
+
+const COLORS: &[Color] = &[
+    Color::Black,
+    Color::Black,
+    Color::Grey,
+    Color::Blue,
+    Color::Red,
+];
+
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    pub folders: usize,
+    pub notebooks: usize,
+    pub pdfs: usize,
+    pub epubs: usize,
+    /// Deepest folder nesting, top level folders have depth 1
+    pub max_depth: usize,
+    pub max_pages: usize,
+    pub max_strokes: usize,
+    /// Share of documents flagged `deleted`
+    pub deleted: f64,
+    /// Share of documents whose parent is the trash
+    pub trashed: f64,
+    pub seed: u64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            folders: 4,
+            notebooks: 8,
+            pdfs: 2,
+            epubs: 2,
+            max_depth: 3,
+            max_pages: 4,
+            max_strokes: 20,
+            deleted: 0.05,
+            trashed: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    /// A library of about `documents` entries, in the proportions of a real
+    /// tablet: one folder for ten documents, mostly notebooks.
+    pub fn with_documents(documents: usize) -> Self {
+        let folders = (documents / 10).max(1);
+        let notebooks = documents * 6 / 10;
+        let pdfs = documents * 2 / 10;
+
+        GeneratorConfig {
+            folders,
+            notebooks,
+            pdfs,
+            epubs: documents.saturating_sub(folders + notebooks + pdfs),
+            ..Default::default()
+        }
+    }
+}
+
+/// Ids of what [`generate`] wrote.
+#[derive(Debug, Default)]
+pub struct Library {
+    pub folders: Vec<String>,
+    pub documents: Vec<String>,
+}
+
+/// Writes a fake library described by `config` to `root`, which is created
+/// if needed.
+pub fn generate(root: &PathBuf, config: &GeneratorConfig) -> Result<Library> {
+    std::fs::create_dir_all(root)?;
+
+    let mut generator = Generator {
+        root,
+        config,
+        rng: StdRng::seed_from_u64(config.seed),
+        folders: Vec::with_capacity(config.folders),
+    };
+
+    for _ in 0..config.folders {
+        generator.folder()?;
+    }
+
+    let mut documents = Vec::with_capacity(config.notebooks + config.pdfs + config.epubs);
+    for _ in 0..config.notebooks {
+        documents.push(generator.notebook()?);
+    }
+    for _ in 0..config.pdfs {
+        documents.push(generator.pdf()?);
+    }
+    for _ in 0..config.epubs {
+        documents.push(generator.epub()?);
+    }
+
+    Ok(Library {
+        folders: generator.folders.into_iter().map(|(id, _)| id).collect(),
+        documents,
+    })
+}
+
+struct Generator<'a> {
+    root: &'a PathBuf,
+    config: &'a GeneratorConfig,
+    rng: StdRng,
+    /// Id and depth of the folders written so far
+    folders: Vec<(String, usize)>,
+}
+
+impl Generator<'_> {
+    fn folder(&mut self) -> Result<()> {
+        let id = self.id();
+
+        let parents: Vec<&(String, usize)> = self
+            .folders
+            .iter()
+            .filter(|(_, depth)| *depth < self.config.max_depth)
+            .collect();
+        let (parent, depth) = match parents.choose(&mut self.rng) {
+            Some((parent, depth)) if self.rng.gen_bool(0.7) => (parent.clone(), depth + 1),
+            _ => (String::new(), 1),
+        };
+
+        let mut metadata = Metadata::new(COLLECTION_TYPE, &parent, &self.name());
+        self.stamp(&mut metadata);
+        write_metadata_with_id(self.root, &id, &metadata)?;
+        write_content_with_id(self.root, &id, &Content::default())?;
+
+        self.folders.push((id, depth));
+        Ok(())
+    }
+
+    fn notebook(&mut self) -> Result<String> {
+        let id = self.id();
+        let pages = self.pages();
+
+        let page_ids: Vec<String> = pages.iter().map(|_| self.id()).collect();
+        for (page_id, page) in page_ids.iter().zip(&pages) {
+            write_page_with_id(self.root, &id, page_id, page)?;
+        }
+
+        let pagedata: Vec<String> = pages
+            .iter()
+            .map(|_| TEMPLATES.choose(&mut self.rng).unwrap().to_string())
+            .collect();
+        write_pagedata_with_id(self.root, &id, &pagedata)?;
+
+        let content = self.content("notebook", page_ids, None);
+        write_content_with_id(self.root, &id, &content)?;
+        let name = self.name();
+        self.document(&id, &name)?;
+
+        Ok(id)
+    }
+
+    fn pdf(&mut self) -> Result<String> {
+        let id = self.id();
+
+        let pdf = self.render()?;
+        std::fs::write(self.root.join(format!("{}.pdf", id)), &pdf)?;
+
+        let page_ids = (0..pdf_pages(&pdf)).map(|_| self.id()).collect();
+        let content = self.content("pdf", page_ids, Some(pdf.len()));
+        write_content_with_id(self.root, &id, &content)?;
+        let name = self.name();
+        self.document(&id, &name)?;
+
+        Ok(id)
+    }
+
+    /// xochitl keeps the original EPUB next to the PDF it converted it to.
+    fn epub(&mut self) -> Result<String> {
+        let id = self.id();
+        let title = self.name();
+
+        let epub = epub(&title);
+        std::fs::write(self.root.join(format!("{}.epub", id)), &epub)?;
+
+        let pdf = self.render()?;
+        std::fs::write(self.root.join(format!("{}.pdf", id)), &pdf)?;
+
+        let page_ids = (0..pdf_pages(&pdf)).map(|_| self.id()).collect();
+        let content = self.content("epub", page_ids, Some(epub.len()));
+        write_content_with_id(self.root, &id, &content)?;
+        self.document(&id, &title)?;
+
+        Ok(id)
+    }
+
+    /// Writes the metadata of document `id`, in a random folder.
+    fn document(&mut self, id: &str, name: &str) -> Result<()> {
+        let parent = if self.rng.gen_bool(self.config.trashed) {
+            TRASH.to_string()
+        } else {
+            match self.folders.choose(&mut self.rng) {
+                Some((folder, _)) if self.rng.gen_bool(0.8) => folder.clone(),
+                _ => String::new(),
+            }
+        };
+
+        let mut metadata = Metadata::new(DOCUMENT_TYPE, &parent, name);
+        metadata._deleted = self.rng.gen_bool(self.config.deleted);
+        metadata._pinned = self.rng.gen_bool(0.1);
+        self.stamp(&mut metadata);
+
+        let opened = metadata.last_modified + Duration::from_secs(self.rng.gen_range(0..YEAR / 12));
+        metadata.last_opened = Some(opened);
+        metadata.last_opened_page = Some(0);
+
+        write_metadata_with_id(self.root, id, &metadata)
+    }
+
+    /// Marks `metadata` as synced from the device, as in a real dump.
+    fn stamp(&mut self, metadata: &mut Metadata) {
+        metadata.last_modified =
+            UNIX_EPOCH + Duration::from_secs(NOW - self.rng.gen_range(0..YEAR));
+        metadata._version = self.rng.gen_range(1..40);
+        metadata._metadatamodified = false;
+        metadata._modified = false;
+        metadata._synced = true;
+    }
+
+    fn content(&mut self, file_type: &str, pages: Vec<String>, size: Option<usize>) -> Content {
+        Content {
+            file_type: Some(file_type.to_string()),
+            page_count: pages.len(),
+            pages,
+            orientation: if self.rng.gen_bool(0.8) {
+                "portrait"
+            } else {
+                "landscape"
+            }
+            .to_string(),
+            tags: Vec::new(),
+            size_in_bytes: size.map(|size| size.to_string()),
+        }
+    }
+
+    /// Renders random pages to stand in for an imported PDF.
+    fn render(&mut self) -> Result<Vec<u8>> {
+        let pages = self.pages();
+        let notebook = Notebook {
+            metadata: Metadata::new(DOCUMENT_TYPE, "", ""),
+            content: Content::default(),
+            pagedata: vec![TEMPLATES[0].to_string(); pages.len()],
+            pages,
+        };
+
+        let mut pdf = Vec::new();
+        notebook.render(&mut pdf)?;
+        Ok(pdf)
+    }
+
+    fn pages(&mut self) -> Vec<Page> {
+        let count = self.rng.gen_range(1..=self.config.max_pages.max(1));
+        (0..count).map(|_| self.page()).collect()
+    }
+
+    fn page(&mut self) -> Page {
+        let layers = self.rng.gen_range(1..=2);
+
+        Page {
+            layers: (0..layers)
+                .map(|_| Layer {
+                    lines: (0..self.rng.gen_range(0..=self.config.max_strokes))
+                        .map(|_| self.line())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// A random walk across the page, smooth enough to look handwritten.
+    fn line(&mut self) -> Line {
+        let count = self.rng.gen_range(2..40);
+        let mut x = self.rng.gen_range(0. ..REMARKABLE_WIDTH);
+        let mut y = self.rng.gen_range(0. ..REMARKABLE_HEIGHT);
+        let mut direction: f32 = self.rng.gen_range(0. ..std::f32::consts::TAU);
+
+        let points = (0..count)
+            .map(|_| {
+                direction += self.rng.gen_range(-0.5..0.5);
+                let speed = self.rng.gen_range(1. ..8.);
+                x = (x + speed * direction.cos()).clamp(0., REMARKABLE_WIDTH);
+                y = (y + speed * direction.sin()).clamp(0., REMARKABLE_HEIGHT);
+
+                Point {
+                    x,
+                    y,
+                    speed,
+                    direction,
+                    width: self.rng.gen_range(1. ..4.),
+                    pressure: self.rng.gen_range(0.1..1.),
+                }
+            })
+            .collect();
+
+        Line {
+            brush_type: *BRUSHES.choose(&mut self.rng).unwrap(),
+            color: *COLORS.choose(&mut self.rng).unwrap(),
+            unknown_line_attribute: 0,
+            unknown_line_attribute_2: 0,
+            brush_base_size: *[1.875, 2., 2.125].choose(&mut self.rng).unwrap(),
+            points,
+        }
+    }
+
+    fn id(&mut self) -> String {
+        uuid::Builder::from_random_bytes(self.rng.gen())
+            .into_uuid()
+            .to_string()
+    }
+
+    /// Names are drawn from a small vocabulary, so siblings sometimes share
+    /// one like on a real tablet.
+    fn name(&mut self) -> String {
+        if self.rng.gen_bool(0.01) {
+            return ODD_NAMES.choose(&mut self.rng).unwrap().to_string();
+        }
+
+        let count = self.rng.gen_range(1..=3);
+        let words: Vec<&str> = WORDS
+            .choose_multiple(&mut self.rng, count)
+            .copied()
+            .collect();
+        words.join(" ")
+    }
+}
+
+/// Pages of a PDF written by [`Notebook::render`].
+fn pdf_pages(pdf: &[u8]) -> usize {
+    lopdf::Document::load_mem(pdf)
+        .map(|document| document.get_pages().len())
+        .unwrap_or(1)
+}
+
+/// A minimal valid EPUB, an uncompressed zip archive with a single chapter.
+fn epub(title: &str) -> Vec<u8> {
+    let files = [
+        ("mimetype", "application/epub+zip".to_string()),
+        (
+            "META-INF/container.xml",
+            r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#
+            .to_string(),
+        ),
+        (
+            "content.opf",
+            format!(
+                r#"<?xml version="1.0"?>
+<package version="3.0" xmlns="http://www.idpf.org/2007/opf" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="id">{title}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>en</dc:language>
+  </metadata>
+  <manifest>
+    <item id="chapter" href="chapter.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="chapter"/>
+  </spine>
+</package>
+"#,
+                title = escape(title)
+            ),
+        ),
+        (
+            "chapter.xhtml",
+            format!(
+                r#"<?xml version="1.0"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+  <head><title>{title}</title></head>
+  <body><h1>{title}</h1><p>Generated for tests.</p></body>
+</html>
+"#,
+                title = escape(title)
+            ),
+        ),
+    ];
+
+    let mut zip = Vec::new();
+    let mut central = Vec::new();
+
+    for (name, data) in &files {
+        let crc = crc32(data.as_bytes());
+        let offset = zip.len() as u32;
+
+        // Local file header, stored without compression
+        zip_header(&mut zip, 0x04034b50, name, data.len(), crc, None);
+        zip.extend_from_slice(data.as_bytes());
+
+        zip_header(
+            &mut central,
+            0x02014b50,
+            name,
+            data.len(),
+            crc,
+            Some(offset),
+        );
+    }
+
+    let central_offset = zip.len() as u32;
+    zip.extend_from_slice(&central);
+
+    // End of central directory
+    zip.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    zip.extend_from_slice(&[0; 4]);
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&central_offset.to_le_bytes());
+    zip.extend_from_slice(&[0; 2]);
+
+    zip
+}
+
+/// Local (`offset` is `None`) or central directory header of a stored entry.
+fn zip_header(
+    out: &mut Vec<u8>,
+    signature: u32,
+    name: &str,
+    size: usize,
+    crc: u32,
+    offset: Option<u32>,
+) {
+    out.extend_from_slice(&signature.to_le_bytes());
+    if offset.is_some() {
+        // Version made by
+        out.extend_from_slice(&20u16.to_le_bytes());
+    }
+    // Version needed, then flags, method (stored), time and date
+    out.extend_from_slice(&20u16.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&crc.to_le_bytes());
+    out.extend_from_slice(&(size as u32).to_le_bytes());
+    out.extend_from_slice(&(size as u32).to_le_bytes());
+    out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    // Extra field length
+    out.extend_from_slice(&[0; 2]);
+
+    if let Some(offset) = offset {
+        // Comment length, disk, internal and external attributes
+        out.extend_from_slice(&[0; 10]);
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb88320 & (!(crc & 1)).wrapping_add(1))
+        })
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{read_metadata_with_id, read_notebook, Result};
+
+    use super::{crc32, generate, GeneratorConfig};
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn generated_library_reads_back() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("rmk-generate-{}", std::process::id()));
+        let config = GeneratorConfig::with_documents(50);
+
+        let library = generate(&root, &config)?;
+        assert_eq!(library.folders.len() + library.documents.len(), 50);
+
+        for id in &library.documents[..config.notebooks] {
+            let mut pdf = Vec::new();
+            read_notebook(&root, id)?.render(&mut pdf)?;
+            assert!(!pdf.is_empty());
+        }
+
+        let again = std::env::temp_dir().join(format!("rmk-generate-{}-2", std::process::id()));
+        let same = generate(&again, &config)?;
+        assert_eq!(same.documents, library.documents);
+        assert_eq!(
+            read_metadata_with_id(&again, &same.documents[0])?.visible_name,
+            read_metadata_with_id(&root, &library.documents[0])?.visible_name
+        );
+
+        std::fs::remove_dir_all(root)?;
+        std::fs::remove_dir_all(again)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+//! Single pages of a notebook as SVG or PNG images, one pixel per point of the
+//! reMarkable screen at scale 1.
+
+use std::io::Write;
+
+use crate::{
+    rm::{BrushType, Color, Line, Page},
+    Error, Notebook, Result,
+};
+
+const SCREEN_WIDTH: f32 = 1404.;
+const SCREEN_HEIGHT: f32 = 1872.;
+
+const HIGHLIGHTER_OPACITY: f32 = 0.35;
+const HIGHLIGHTER_RGB: [u8; 3] = [255, 235, 59];
+
+/// How a stroke is drawn.
+struct Style {
+    rgb: [u8; 3],
+    width: f32,
+    opacity: f32,
+}
+
+/// `None` for strokes that leave no ink, such as selections.
+fn style(line: &Line) -> Option<Style> {
+    let opacity = match line.brush_type {
+        BrushType::EraseArea | BrushType::EraseAll | BrushType::SelectionBrush => return None,
+        BrushType::Highlighter => HIGHLIGHTER_OPACITY,
+        _ => 1.,
+    };
+
+    let rgb = match (line.brush_type, line.color) {
+        // Erasers of older versions draw white over the strokes below
+        (BrushType::Eraser, _) | (_, Color::White) => [255, 255, 255],
+        (BrushType::Highlighter, _) => HIGHLIGHTER_RGB,
+        (_, Color::Black) => [0, 0, 0],
+        (_, Color::Grey) => [191, 191, 191],
+        (_, Color::Blue) => [0, 98, 204],
+        (_, Color::Red) => [217, 7, 7],
+    };
+
+    let width = if line.points.is_empty() {
+        0.
+    } else {
+        line.points.iter().map(|point| point.width).sum::<f32>() / line.points.len() as f32
+    };
+
+    Some(Style {
+        rgb,
+        width: width.max(1.),
+        opacity,
+    })
+}
+
+impl Notebook {
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Writes page `page` as an SVG document of the size of the screen.
+    pub fn render_svg<W: Write>(&self, page: usize, target: &mut W) -> Result<()> {
+        let page = self.page(page)?;
+        write_svg(page.lines().map(|line| (line, None)), target)
+    }
+
+    /// Writes page `page` as an RGB PNG, `scale` times the size of the screen.
+    pub fn render_png<W: Write>(&self, page: usize, scale: f32, target: &mut W) -> Result<()> {
+        let page = self.page(page)?;
+
+        let mut canvas = Canvas::new(
+            (SCREEN_WIDTH * scale).round().max(1.) as usize,
+            (SCREEN_HEIGHT * scale).round().max(1.) as usize,
+        );
+        for line in page.lines() {
+            if let Some(style) = style(line) {
+                canvas.stroke(line, &style, scale);
+            }
+        }
+
+        let mut encoder = png::Encoder::new(target, canvas.width as u32, canvas.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&canvas.pixels))
+            .map_err(|e| Error::WriteError(e.to_string()))
+    }
+
+    fn page(&self, page: usize) -> Result<&Page> {
+        self.pages
+            .get(page)
+            .ok_or_else(|| Error::InvalidPages(format!("page {} of {}", page, self.pages.len())))
+    }
+}
+
+/// Writes an SVG document of the size of the screen with `lines` drawn in
+/// order, in their own color or in the one given with them.
+pub(crate) fn write_svg<'a, W: Write>(
+    lines: impl IntoIterator<Item = (&'a Line, Option<[u8; 3]>)>,
+    target: &mut W,
+) -> Result<()> {
+    writeln!(target, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        target,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = SCREEN_WIDTH,
+        h = SCREEN_HEIGHT
+    )?;
+    writeln!(target, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+
+    for (line, rgb) in lines {
+        let style = match style(line) {
+            Some(style) if !line.points.is_empty() => match rgb {
+                Some(rgb) => Style {
+                    rgb,
+                    opacity: 1.,
+                    ..style
+                },
+                None => style,
+            },
+            _ => continue,
+        };
+
+        let mut points: Vec<String> = line
+            .points
+            .iter()
+            .map(|point| format!("{:.2},{:.2}", point.x, point.y))
+            .collect();
+        // A single point is drawn as a dot by the round caps
+        if points.len() == 1 {
+            points.push(points[0].clone());
+        }
+
+        writeln!(
+            target,
+            r##"<polyline fill="none" stroke="#{:02x}{:02x}{:02x}" stroke-width="{:.2}" stroke-opacity="{}" stroke-linecap="round" stroke-linejoin="round" points="{}"/>"##,
+            style.rgb[0],
+            style.rgb[1],
+            style.rgb[2],
+            style.width,
+            style.opacity,
+            points.join(" ")
+        )?;
+    }
+
+    writeln!(target, "</svg>")?;
+    Ok(())
+}
+
+/// A white RGB image strokes are drawn on, antialiased.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![255; width * height * 3],
+        }
+    }
+
+    fn stroke(&mut self, line: &Line, style: &Style, scale: f32) {
+        let points: Vec<(f32, f32)> = line
+            .points
+            .iter()
+            .map(|point| (point.x * scale, point.y * scale))
+            .collect();
+        let radius = (style.width * scale / 2.).max(0.5);
+
+        let bounds = match line.bounding_box() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let clamp = |value: f32, max: usize| (value.max(0.) as usize).min(max);
+        let (x0, x1) = (
+            clamp(bounds.min_x * scale - radius - 1., self.width),
+            clamp(bounds.max_x * scale + radius + 2., self.width),
+        );
+        let (y0, y1) = (
+            clamp(bounds.min_y * scale - radius - 1., self.height),
+            clamp(bounds.max_y * scale + radius + 2., self.height),
+        );
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        // Coverage of the whole stroke, so that overlapping segments of a
+        // translucent stroke are not blended twice
+        let mask_width = x1 - x0;
+        let mut mask = vec![0_f32; mask_width * (y1 - y0)];
+
+        let segments = points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain((points.len() == 1).then(|| (points[0], points[0])));
+
+        for (a, b) in segments {
+            let sx0 = clamp(a.0.min(b.0) - radius - 1., x1).max(x0);
+            let sx1 = clamp(a.0.max(b.0) + radius + 2., x1);
+            let sy0 = clamp(a.1.min(b.1) - radius - 1., y1).max(y0);
+            let sy1 = clamp(a.1.max(b.1) + radius + 2., y1);
+
+            for y in sy0..sy1 {
+                for x in sx0..sx1 {
+                    let center = (x as f32 + 0.5, y as f32 + 0.5);
+                    let coverage = (radius + 0.5 - distance(center, a, b)).clamp(0., 1.);
+
+                    let cell = &mut mask[(y - y0) * mask_width + (x - x0)];
+                    *cell = cell.max(coverage);
+                }
+            }
+        }
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let alpha = mask[(y - y0) * mask_width + (x - x0)] * style.opacity;
+                if alpha <= 0. {
+                    continue;
+                }
+
+                let pixel = (y * self.width + x) * 3;
+                for (channel, value) in style.rgb.iter().enumerate() {
+                    let current = self.pixels[pixel + channel] as f32;
+                    self.pixels[pixel + channel] =
+                        (current + (*value as f32 - current) * alpha).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Distance from `p` to segment `ab`.
+fn distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+
+    let t = if length == 0. {
+        0.
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0., 1.)
+    };
+
+    let (x, y) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    (x * x + y * y).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{read_notebook, Error, Result};
+
+    #[test]
+    fn svg_and_png() -> Result<()> {
+        let root = PathBuf::from("samples");
+        let notebook = read_notebook(&root, "0d9af7de-39f8-4251-8500-330eec0d00f0")?;
+
+        let mut svg = Vec::new();
+        notebook.render_svg(0, &mut svg)?;
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains("<polyline"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+
+        let mut png = Vec::new();
+        notebook.render_png(0, 0.25, &mut png)?;
+        assert!(png.starts_with(b"\x89PNG"));
+
+        assert!(matches!(
+            notebook.render_svg(notebook.page_count(), &mut Vec::new()),
+            Err(Error::InvalidPages(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! Imports PDF and EPUB files into a xochitl root, as the desktop app does
+//! when uploading: the original file next to its metadata and content.
+//! xochitl renders thumbnails, and converts EPUBs to PDF, on the device.
+
+use std::path::Path;
+
+use uuid::Uuid;
+
+use crate::{
+    notebook::{write_content_with_id, write_metadata_with_id},
+    Content, Error, Metadata, Result, DOCUMENT_TYPE,
+};
+
+const PDF_TYPE: &str = "pdf";
+const EPUB_TYPE: &str = "epub";
+
+/// Copies `file` into `root` as a new document of folder `parent`, named
+/// `name` or after the file, and returns its id.
+pub fn import(root: &Path, file: &Path, parent: &str, name: Option<&str>) -> Result<String> {
+    let file_type = match file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some(PDF_TYPE) => PDF_TYPE,
+        Some(EPUB_TYPE) => EPUB_TYPE,
+        _ => {
+            return Err(Error::UnsupportedDocument(format!(
+                "{} is neither a PDF nor an EPUB",
+                file.display()
+            )))
+        }
+    };
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .ok_or_else(|| Error::InvalidPath(file.display().to_string()))?,
+    };
+
+    let data = std::fs::read(file)?;
+
+    // xochitl lists a page id per page of the PDF, EPUBs get theirs once
+    // converted
+    let page_count = match file_type {
+        PDF_TYPE => lopdf::Document::load_mem(&data)
+            .map_err(|e| Error::UnsupportedDocument(format!("{}: {}", file.display(), e)))?
+            .get_pages()
+            .len(),
+        _ => 0,
+    };
+
+    let id = Uuid::new_v4().to_string();
+
+    let target = root.join(format!("{}.{}", id, file_type));
+    std::fs::write(&target, &data)
+        .map_err(|e| Error::WriteError(format!("{}: {}", target.display(), e)))?;
+    std::fs::create_dir_all(root.join(&id))?;
+
+    let content = Content {
+        file_type: Some(file_type.to_string()),
+        page_count,
+        pages: (0..page_count)
+            .map(|_| Uuid::new_v4().to_string())
+            .collect(),
+        orientation: "portrait".to_string(),
+        size_in_bytes: Some(data.len().to_string()),
+        ..Default::default()
+    };
+    write_content_with_id(root, &id, &content)?;
+
+    // Written last, xochitl and RmkFs only pick up documents with metadata
+    write_metadata_with_id(root, &id, &Metadata::new(DOCUMENT_TYPE, parent, &name))?;
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::import;
+    use crate::{read_content_with_id, read_metadata_with_id, Error, Result};
+
+    #[test]
+    fn import_pdf() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("rmk-import-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+
+        let pdf = PathBuf::from("samples").join("Hedged shared class.pdf");
+        let id = import(&root, &pdf, "", None)?;
+
+        let metadata = read_metadata_with_id(&root, &id)?;
+        assert_eq!(metadata.visible_name, "Hedged shared class");
+        assert_eq!(metadata.parent, "");
+
+        let content = read_content_with_id(&root, &id)?;
+        assert_eq!(content.file_type.as_deref(), Some("pdf"));
+        assert!(content.page_count > 0);
+        assert_eq!(content.pages.len(), content.page_count);
+        assert_eq!(
+            std::fs::read(root.join(format!("{}.pdf", id)))?,
+            std::fs::read(&pdf)?
+        );
+
+        assert!(matches!(
+            import(&root, Path::new("notes.txt"), "", None),
+            Err(Error::UnsupportedDocument(_))
+        ));
+
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,26 @@
-use std::path::PathBuf;
+use std::path::Path;
 
+pub mod diff;
+pub mod edit;
 pub mod errors;
+pub mod generate;
+mod image;
+pub mod import;
 mod notebook;
 mod parse;
 mod render;
 mod rm;
+mod write;
 
 pub use errors::*;
 use notebook::read_metadata_with_id;
 pub use notebook::{
-    read_content_with_id, read_metadata, read_pagedata_with_id, read_rm, Content, Metadata,
+    create_collection, read_content_with_id, read_metadata, read_page_with_id,
+    read_pagedata_with_id, read_rm, write_content_with_id, write_metadata_with_id,
+    write_page_with_id, write_pagedata_with_id, Content, Metadata, Tag, COLLECTION_TYPE,
+    DOCUMENT_TYPE,
 };
-use rm::Page;
+pub use rm::{BoundingBox, BrushType, Color, Layer, Line, LinesData, Page, Point};
 
 pub struct Notebook {
     metadata: Metadata,
@@ -20,7 +29,7 @@ pub struct Notebook {
     pages: Vec<Page>,
 }
 
-pub fn read_notebook(root: &PathBuf, id: &str) -> Result<Notebook> {
+pub fn read_notebook(root: &Path, id: &str) -> Result<Notebook> {
     let metadata = read_metadata_with_id(root, id)?;
     let content = read_content_with_id(root, id)?;
     let pagedata = read_pagedata_with_id(root, id)?;
@@ -33,3 +42,14 @@ pub fn read_notebook(root: &PathBuf, id: &str) -> Result<Notebook> {
         pages,
     })
 }
+
+impl Notebook {
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Template of each page, in the order of [`Content::pages`].
+    pub fn pagedata(&self) -> &[String] {
+        &self.pagedata
+    }
+}
@@ -1,38 +1,61 @@
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
-    path::PathBuf,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
     time::SystemTime,
 };
 
 use log::info;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
 use serde_with::serde_as;
+use uuid::Uuid;
 
 use crate::{
     rm::{LinesData, Page},
-    Result,
+    Error, Result,
 };
 
 #[serde_as]
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Content {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_type: Option<String>,
+    // Folders have an empty content file
+    #[serde(default)]
     pub page_count: usize,
+    #[serde(default)]
     pub pages: Vec<String>,
+    #[serde(default)]
     pub orientation: String,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// Stored as a string by xochitl
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_in_bytes: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+}
+
+pub const COLLECTION_TYPE: &str = "CollectionType";
+pub const DOCUMENT_TYPE: &str = "DocumentType";
+
 #[serde_as]
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Metadata {
     pub _deleted: bool,
-    // #[serde_as(as = "serde_with::TimestampSeconds<String>")]
-    // last_opened: SystemTime,
-    // last_opened_page: usize,
-    #[serde_as(as = "serde_with::TimestampSeconds<String>")]
+    // Folders are never opened
+    #[serde_as(as = "Option<serde_with::TimestampMilliSeconds<String>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_opened: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_opened_page: Option<usize>,
+    #[serde_as(as = "serde_with::TimestampMilliSeconds<String>")]
     pub last_modified: SystemTime,
     pub _metadatamodified: bool,
     pub _modified: bool,
@@ -46,6 +69,29 @@ pub struct Metadata {
     pub visible_name: String,
 }
 
+impl Metadata {
+    pub fn new(typ: &str, parent: &str, visible_name: &str) -> Self {
+        Metadata {
+            _deleted: false,
+            last_opened: None,
+            last_opened_page: None,
+            last_modified: SystemTime::now(),
+            _metadatamodified: true,
+            _modified: true,
+            parent: parent.to_string(),
+            _pinned: false,
+            _synced: false,
+            typ: typ.to_string(),
+            _version: 1,
+            visible_name: visible_name.to_string(),
+        }
+    }
+
+    pub fn is_collection(&self) -> bool {
+        self.typ == COLLECTION_TYPE
+    }
+}
+
 pub fn read_metadata(path: &PathBuf) -> Result<(&str, Metadata)> {
     let file = std::fs::read_to_string(path)?;
     let metadata = serde_json::from_str(&file)?;
@@ -60,19 +106,96 @@ pub fn read_metadata(path: &PathBuf) -> Result<(&str, Metadata)> {
     Ok((id, metadata))
 }
 
-pub fn read_metadata_with_id(root: &PathBuf, id: &str) -> Result<Metadata> {
+pub fn read_metadata_with_id(root: &Path, id: &str) -> Result<Metadata> {
     let file = std::fs::read_to_string(root.join(format!("{}.metadata", id)))?;
     let metadata = serde_json::from_str(&file)?;
     Ok(metadata)
 }
 
-pub fn read_content_with_id(root: &PathBuf, id: &str) -> Result<Content> {
+/// Writes `metadata` to `<root>/<id>.metadata`, keeping any key of an
+/// existing file that `Metadata` does not model (e.g. `deviceName`).
+pub fn write_metadata_with_id(root: &Path, id: &str, metadata: &Metadata) -> Result<()> {
+    merge_json(&root.join(format!("{}.metadata", id)), metadata)
+}
+
+/// Writes `content` to `<root>/<id>.content`, keeping any key of an existing
+/// file that `Content` does not model (e.g. `extraMetadata`).
+pub fn write_content_with_id(root: &Path, id: &str, content: &Content) -> Result<()> {
+    merge_json(&root.join(format!("{}.content", id)), content)
+}
+
+/// Writes the template of each page, one per line.
+pub fn write_pagedata_with_id(root: &Path, id: &str, pagedata: &[String]) -> Result<()> {
+    let path = root.join(format!("{}.pagedata", id));
+    let mut file = pagedata.join("\n");
+    if !pagedata.is_empty() {
+        file.push('\n');
+    }
+
+    std::fs::write(&path, file).map_err(|e| Error::WriteError(format!("{}: {}", path.display(), e)))
+}
+
+/// Writes page `page` of document `id` to `<root>/<id>/<page>.rm`.
+pub fn write_page_with_id(root: &Path, id: &str, page_id: &str, page: &Page) -> Result<()> {
+    let dir = root.join(id);
+    std::fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!("{}.rm", page_id));
+    let mut file = io::BufWriter::new(File::create(&path)?);
+    page.write(&mut file)?;
+
+    io::Write::flush(&mut file).map_err(|e| Error::WriteError(format!("{}: {}", path.display(), e)))
+}
+
+/// Updates the json object at `path` with the fields of `value`.
+fn merge_json<T: Serialize>(path: &PathBuf, value: &T) -> Result<()> {
+    let mut existing = match std::fs::read_to_string(path) {
+        Ok(file) => serde_json::from_str(&file)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Value::Object(Default::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if let (Some(fields), Value::Object(updated)) =
+        (existing.as_object_mut(), serde_json::to_value(value)?)
+    {
+        fields.extend(updated);
+    }
+
+    write_json(path, &existing)
+}
+
+/// Creates a new folder in `root` and returns its generated id.
+pub fn create_collection(root: &Path, parent: &str, name: &str) -> Result<(String, Metadata)> {
+    let id = Uuid::new_v4().to_string();
+    let metadata = Metadata::new(COLLECTION_TYPE, parent, name);
+
+    write_metadata_with_id(root, &id, &metadata)?;
+    write_json(
+        &root.join(format!("{}.content", id)),
+        &Value::Object(Default::default()),
+    )?;
+
+    Ok((id, metadata))
+}
+
+/// xochitl indents its json files with 4 spaces, keep diffs with the device small.
+pub(crate) fn write_json(path: &Path, value: &Value) -> Result<()> {
+    let mut buffer = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut buffer, PrettyFormatter::with_indent(b"    "));
+    value.serialize(&mut serializer)?;
+
+    std::fs::write(path, buffer)
+        .map_err(|e| Error::WriteError(format!("{}: {}", path.display(), e)))
+}
+
+pub fn read_content_with_id(root: &Path, id: &str) -> Result<Content> {
     let file = std::fs::read_to_string(root.join(format!("{}.content", id)))?;
     let content = serde_json::from_str(&file)?;
     Ok(content)
 }
 
-pub fn read_pagedata_with_id(root: &PathBuf, id: &str) -> Result<Vec<String>> {
+pub fn read_pagedata_with_id(root: &Path, id: &str) -> Result<Vec<String>> {
     let file = File::open(root.join(format!("{}.pagedata", id)))?;
     let pagedata = BufReader::new(file)
         .lines()
@@ -81,7 +204,21 @@ pub fn read_pagedata_with_id(root: &PathBuf, id: &str) -> Result<Vec<String>> {
     Ok(pagedata)
 }
 
-pub fn read_rm(root: &PathBuf, id: &str, pages: &Vec<String>) -> Result<Vec<Page>> {
+/// Strokes of page `page` of document `id`, `None` if the page was never
+/// written on (xochitl does not create a `.rm` file for it).
+pub fn read_page_with_id(root: &Path, id: &str, page: &str) -> Result<Option<Page>> {
+    let path = root.join(id).join(format!("{}.rm", page));
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(LinesData::parse(&mut file)?.pages.pop())
+}
+
+pub fn read_rm(root: &Path, id: &str, pages: &Vec<String>) -> Result<Vec<Page>> {
     let path = root.join(id).join("*.rm");
 
     let mut nb_pages = Vec::with_capacity(pages.len());
@@ -102,14 +239,84 @@ pub fn read_rm(root: &PathBuf, id: &str, pages: &Vec<String>) -> Result<Vec<Page
 #[cfg(test)]
 mod tests {
     use crate::Result;
-    use std::path::PathBuf;
+    use std::{
+        path::PathBuf,
+        time::{Duration, UNIX_EPOCH},
+    };
 
     #[test]
     fn metadata() -> Result<()> {
         let root = PathBuf::from("samples");
         let id = "0d9af7de-39f8-4251-8500-330eec0d00f0";
 
-        let _metadata = super::read_metadata_with_id(&root, id)?;
+        let metadata = super::read_metadata_with_id(&root, id)?;
+        assert_eq!(metadata.last_opened_page, Some(5));
+        assert_eq!(
+            metadata.last_opened,
+            Some(UNIX_EPOCH + Duration::from_millis(1638547442903))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn page_bounding_box() -> Result<()> {
+        let root = PathBuf::from("samples");
+        let id = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+
+        let page = super::read_page_with_id(&root, id, "e3c22b43-bd2c-42d8-8b45-d9bdf829b500")?
+            .expect("sample page has strokes");
+        let bbox = page.bounding_box().expect("sample page is not blank");
+
+        assert!(page.lines().count() > 0);
+        assert!(bbox.min_x <= bbox.max_x && bbox.min_y <= bbox.max_y);
+
+        assert!(super::read_page_with_id(&root, id, "missing")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_page_round_trip() -> Result<()> {
+        let root = PathBuf::from("samples");
+        let id = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+        let page_id = "e3c22b43-bd2c-42d8-8b45-d9bdf829b500";
+
+        let page = super::read_page_with_id(&root, id, page_id)?.unwrap();
+
+        let mut file = Vec::new();
+        page.write(&mut file)?;
+        let written = crate::LinesData::parse(&mut file.as_slice())?
+            .pages
+            .pop()
+            .unwrap();
+
+        assert_eq!(written.lines().count(), page.lines().count());
+        assert_eq!(written.bounding_box(), page.bounding_box());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_metadata_keeps_unknown_keys() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("rmk-notebook-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root)?;
+
+        let id = "0d9af7de-39f8-4251-8500-330eec0d00f0";
+        let source = PathBuf::from("samples").join(format!("{}.metadata", id));
+        std::fs::copy(source, root.join(format!("{}.metadata", id)))?;
+
+        let mut metadata = super::read_metadata_with_id(&root, id)?;
+        metadata.visible_name = "Renamed".to_string();
+        super::write_metadata_with_id(&root, id, &metadata)?;
+
+        let written = super::read_metadata_with_id(&root, id)?;
+        let raw = std::fs::read_to_string(root.join(format!("{}.metadata", id)))?;
+        std::fs::remove_dir_all(&root)?;
+
+        assert_eq!(written.visible_name, "Renamed");
+        assert_eq!(written.last_modified, metadata.last_modified);
+        assert!(raw.contains("\"lastOpened\": \"1638547442903\""));
 
         Ok(())
     }
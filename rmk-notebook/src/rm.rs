@@ -17,10 +17,11 @@ pub struct Layer {
     pub lines: Vec<Line>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BrushType {
     BallPoint,
     Marker,
+    #[default]
     Fineliner,
     SharpPencil,
     TiltPencil,
@@ -34,12 +35,6 @@ pub enum BrushType {
     SelectionBrush,
 }
 
-impl Default for BrushType {
-    fn default() -> BrushType {
-        BrushType::Fineliner
-    }
-}
-
 impl std::convert::TryFrom<i32> for BrushType {
     type Error = Error;
 
@@ -73,8 +68,9 @@ impl std::convert::TryFrom<i32> for BrushType {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
+    #[default]
     Black,
     Grey,
     White,
@@ -97,12 +93,6 @@ impl TryFrom<i32> for Color {
     }
 }
 
-impl Default for Color {
-    fn default() -> Color {
-        Color::Black
-    }
-}
-
 #[derive(Default, Debug)]
 pub struct Line {
     pub brush_type: BrushType,
@@ -123,23 +113,50 @@ pub struct Point {
     pub pressure: f32,
 }
 
-#[derive(Clone)]
-pub struct LayerColor {
-    pub black: String,
-    pub grey: String,
-    pub white: String,
-    pub blue: String,
-    pub red: String,
+/// Smallest rectangle, in reMarkable screen coordinates, containing some points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
 }
 
-impl Default for LayerColor {
-    fn default() -> Self {
-        Self {
-            black: "black".to_string(),
-            grey: "#bfbfbf".to_string(),
-            white: "white".to_string(),
-            blue: "#0062cc".to_string(),
-            red: "#d90707".to_string(),
+impl BoundingBox {
+    pub fn union(self, other: BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
         }
     }
 }
+
+impl Line {
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        self.points
+            .iter()
+            .map(|pt| BoundingBox {
+                min_x: pt.x,
+                min_y: pt.y,
+                max_x: pt.x,
+                max_y: pt.y,
+            })
+            .reduce(BoundingBox::union)
+    }
+}
+
+impl Page {
+    /// Lines of all layers, bottom layer first.
+    pub fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.layers.iter().flat_map(|layer| layer.lines.iter())
+    }
+
+    /// `None` for a blank page.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        self.lines()
+            .filter_map(Line::bounding_box)
+            .reduce(BoundingBox::union)
+    }
+}
@@ -0,0 +1,105 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io;
+
+use crate::rm::{BrushType, Color, Layer, Line, Page, Point};
+use crate::Result;
+
+/// Header of the files written by [`Page::write`], padded to the 43 bytes
+/// [`crate::rm::LinesData::parse`] expects.
+const HEADER: &str = "reMarkable .lines file, version=5";
+const HEADER_LEN: usize = 43;
+
+impl Page {
+    /// Writes the page as a version 5 .rm file, the format xochitl uses for
+    /// every page of a notebook.
+    pub fn write(&self, file: &mut dyn io::Write) -> Result<()> {
+        file.write_all(format!("{:<width$}", HEADER, width = HEADER_LEN).as_bytes())?;
+
+        let mut writer = LinesDataWriter { file };
+        writer.write_page(self)
+    }
+}
+
+struct LinesDataWriter<'a> {
+    file: &'a mut dyn io::Write,
+}
+
+impl LinesDataWriter<'_> {
+    fn write_i32(&mut self, value: i32) -> Result<()> {
+        Ok(self.file.write_i32::<LittleEndian>(value)?)
+    }
+
+    fn write_f32(&mut self, value: f32) -> Result<()> {
+        Ok(self.file.write_f32::<LittleEndian>(value)?)
+    }
+
+    fn write_page(&mut self, page: &Page) -> Result<()> {
+        self.write_i32(page.layers.len() as i32)?;
+        page.layers
+            .iter()
+            .try_for_each(|layer| self.write_layer(layer))
+    }
+
+    fn write_layer(&mut self, layer: &Layer) -> Result<()> {
+        self.write_i32(layer.lines.len() as i32)?;
+        layer
+            .lines
+            .iter()
+            .try_for_each(|line| self.write_line(line))
+    }
+
+    fn write_line(&mut self, line: &Line) -> Result<()> {
+        self.write_i32(line.brush_type.into())?;
+        self.write_i32(line.color.into())?;
+        self.write_i32(line.unknown_line_attribute)?;
+        self.write_f32(line.brush_base_size)?;
+        self.write_i32(line.unknown_line_attribute_2)?;
+
+        self.write_i32(line.points.len() as i32)?;
+        line.points
+            .iter()
+            .try_for_each(|point| self.write_point(point))
+    }
+
+    fn write_point(&mut self, point: &Point) -> Result<()> {
+        self.write_f32(point.x)?;
+        self.write_f32(point.y)?;
+        self.write_f32(point.speed)?;
+        self.write_f32(point.direction)?;
+        self.write_f32(point.width)?;
+        self.write_f32(point.pressure)
+    }
+}
+
+/// Codes of version 5 files, see `TryFrom<i32> for BrushType`.
+impl From<BrushType> for i32 {
+    fn from(brush_type: BrushType) -> i32 {
+        match brush_type {
+            BrushType::Pen => 2,
+            BrushType::Eraser => 6,
+            BrushType::EraseArea => 8,
+            BrushType::EraseAll => 9,
+            BrushType::SelectionBrush => 10,
+            BrushType::Brush => 12,
+            BrushType::SharpPencil => 13,
+            BrushType::TiltPencil => 14,
+            BrushType::BallPoint => 15,
+            BrushType::Marker => 16,
+            BrushType::Fineliner => 17,
+            BrushType::Highlighter => 18,
+            BrushType::Calligraphy => 21,
+        }
+    }
+}
+
+impl From<Color> for i32 {
+    fn from(color: Color) -> i32 {
+        match color {
+            Color::Black => 0,
+            Color::Grey => 1,
+            Color::White => 2,
+            Color::Blue => 6,
+            Color::Red => 7,
+        }
+    }
+}